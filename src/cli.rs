@@ -1,26 +1,208 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
+use crate::diff::{DiffOptions, KeyRule, MatchMode, Normalization};
+use crate::policy::Severity;
+use crate::render::ColorChoice;
+use std::path::PathBuf;
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum View {
     Unified,
     SideBySide,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    /// Human-readable tree, laid out by `--view`
+    Text,
+    /// SARIF 2.1.0 log with one result per change
+    Sarif,
+    /// JUnit XML with one testcase per changed identifiable element
+    Junit,
+    /// One line per change, addressed by AUTOSAR path
+    Paths,
+}
+
 #[derive(Parser, Debug)]
-#[command(name = "arxml-diff")]
+#[command(name = "arxml-diff", args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub args: Option<Args>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print change totals, the most changed tags and packages, element counts and timings
+    Stats(StatsArgs),
+}
+
+// How to read and compare the inputs, shared by the diff and `stats`
+#[derive(clap::Args, Debug)]
+pub struct CompareArgs {
+    /// JSON severity policy to use instead of the built-in AUTOSAR policy
+    #[arg(long, value_name = "FILE")]
+    pub policy: Option<String>,
+
+    /// Also diff comments, processing instructions and CDATA markup
+    #[arg(long)]
+    pub include_comments: bool,
+
+    /// Recover from malformed input (mismatched end tags, bad entities, truncation) and report warnings
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Pair child elements by key regardless of their order
+    #[arg(long)]
+    pub unordered: bool,
+
+    /// Leave elements with this tag out of the diff (repeatable)
+    #[arg(long, value_name = "TAG")]
+    pub ignore_tag: Vec<String>,
+
+    /// Leave attributes with this name out of the diff (repeatable)
+    #[arg(long, value_name = "NAME")]
+    pub ignore_attribute: Vec<String>,
+
+    /// Identify elements with TAG by RULE: short-name, uuid, @ATTRIBUTE or a child tag (repeatable)
+    #[arg(long = "key", value_name = "TAG=RULE", value_parser = parse_key_rule)]
+    pub key_rules: Vec<(String, KeyRule)>,
+
+    /// Treat runs of whitespace in values as a single space
+    #[arg(long)]
+    pub collapse_whitespace: bool,
+
+    /// Compare values case-insensitively
+    #[arg(long)]
+    pub ignore_case: bool,
+
+    /// Compare numerical values by value (0x10 = 16 = 16.0)
+    #[arg(long)]
+    pub normalize_numbers: bool,
+}
+
+#[derive(clap::Args, Debug)]
 pub struct Args {
+    // clap leaves the group of a struct with flattened fields empty; the inputs tell
+    // `Cli` that these arguments are present
+
+    /// Left input: an ARXML file (optionally .gz/.zst compressed), an entry
+    /// `bundle.zip!/path/System.arxml`, or a directory / zip archive to compare as a whole
+    #[arg(group = "Args")]
     pub left: String,
+    /// Right input, of the same kind as the left one
+    #[arg(group = "Args")]
     pub right: String,
 
+    #[command(flatten)]
+    pub compare: CompareArgs,
+
     #[arg(long, short)]
     pub interactive: bool,
 
+    /// Open the TUI in review mode, keeping reviewed marks and notes in FILE
+    /// (default: RIGHT.review.json)
+    #[arg(long, value_name = "FILE", require_equals = true, conflicts_with_all = ["quiet", "brief", "summary"])]
+    pub review: Option<Option<PathBuf>>,
+
+    /// Open the TUI in merge mode, writing the merged ARXML to FILE
+    /// (default: RIGHT with a .merged.arxml suffix)
+    #[arg(long, value_name = "FILE", require_equals = true, conflicts_with_all = ["quiet", "brief", "summary"])]
+    pub merge: Option<Option<PathBuf>>,
+
+    /// Print nothing; only report through the exit status
+    #[arg(long, short, conflicts_with_all = ["interactive", "brief"])]
+    pub quiet: bool,
+
+    /// Only report whether the inputs differ, with summary counts
+    #[arg(long, short, conflicts_with = "interactive")]
+    pub brief: bool,
+
+    /// Exit with status 1 only for changes of at least this severity
+    #[arg(long, value_enum, value_name = "SEVERITY")]
+    pub fail_on: Option<Severity>,
+
     #[arg(long, value_enum, default_value_t = View::Unified)]
     pub view: View,
+
+    /// Print only changed elements and their ancestors, collapsing unchanged siblings
+    #[arg(long)]
+    pub changes_only: bool,
+
+    /// With --changes-only, also print N unchanged siblings around each change (implies --changes-only)
+    #[arg(long, value_name = "N")]
+    pub context: Option<usize>,
+
+    /// In the side-by-side view, wrap long names and values instead of truncating them
+    #[arg(long)]
+    pub wrap: bool,
+
+    /// Output format for non-interactive runs
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Colour the output: auto (terminal and no NO_COLOR), always or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Follow the output with the statistics of `stats`
+    #[arg(long, conflicts_with = "interactive")]
+    pub summary: bool,
 }
 
-impl Args {
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    /// Left input, as for the diff
+    pub left: String,
+    /// Right input
+    pub right: String,
+
+    #[command(flatten)]
+    pub compare: CompareArgs,
+
+    /// Number of most changed tags and AR-PACKAGEs to list
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub top: usize,
+}
+
+fn parse_key_rule(s: &str) -> Result<(String, KeyRule), String> {
+    let (tag, rule) = s.split_once('=').ok_or_else(|| format!("expected TAG=RULE, got `{}`", s))?;
+    Ok((tag.to_string(), rule.parse()?))
+}
+
+impl Cli {
     pub fn parse_args() -> Self {
-        Args::parse()
+        Cli::parse()
+    }
+}
+
+impl Args {
+    /// Unchanged siblings to keep around changes, or `None` to print the whole tree.
+    pub fn context(&self) -> Option<usize> {
+        self.context.or(self.changes_only.then_some(0))
+    }
+}
+
+impl CompareArgs {
+    pub fn diff_options(&self) -> DiffOptions {
+        let mut builder = DiffOptions::builder()
+            .include_comments(self.include_comments)
+            .matching(if self.unordered { MatchMode::Unordered } else { MatchMode::Ordered })
+            .normalization(Normalization {
+                collapse_whitespace: self.collapse_whitespace,
+                ignore_case: self.ignore_case,
+                numbers: self.normalize_numbers,
+            });
+        for tag in &self.ignore_tag {
+            builder = builder.ignore_tag(tag);
+        }
+        for name in &self.ignore_attribute {
+            builder = builder.ignore_attribute(name);
+        }
+        for (tag, rule) in &self.key_rules {
+            builder = builder.key_rule(tag, rule.clone());
+        }
+        builder.build()
     }
 }
@@ -1,4 +1,6 @@
-use crate::parse::Element;
+use crate::parse::{ContentNode, Document, Element, Position, XSI_NAMESPACE};
+use crate::policy::Severity;
+use crate::stats::Counts;
 use similar::{capture_diff_slices, Algorithm};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -14,110 +16,681 @@ pub struct DiffNode {
     pub status: NodeStatus,
     pub tag: String,
     pub key: Option<String>,
+    /// AUTOSAR path (SHORT-NAMEs from the root) of the element, or of its nearest
+    /// ancestor with a SHORT-NAME
+    pub path: String,
+    /// Attribute and content values that differ, for elements present on both sides
+    pub changes: Vec<ValueChange>,
+    /// Set by [`crate::policy::Policy::apply`] on each change
+    pub severity: Option<Severity>,
     pub children: Vec<DiffNode>,
     /// Serialized XML of the left-hand element (if present) for raw view
     pub left_xml: Option<String>,
     /// Serialized XML of the right-hand element (if present) for raw view
     pub right_xml: Option<String>,
+    /// Source position of the left-hand element, if present
+    pub left_pos: Option<Position>,
+    /// Source position of the right-hand element, if present
+    pub right_pos: Option<Position>,
+    /// UUID of the left-hand element, if present and it has one
+    pub left_uuid: Option<String>,
+    /// UUID of the right-hand element, if present and it has one
+    pub right_uuid: Option<String>,
 }
 
-fn key_of(e: &Element) -> String {
-    if let Some(sn) = &e.short_name { return sn.clone(); }
-    if let Some(u) = &e.uuid { return u.clone(); }
-    e.tag.clone()
+/// What a single change does to an element.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// Attribute or content values of the element itself changed
+    Changed,
 }
 
-fn diff_elements(left: Option<&Element>, right: Option<&Element>) -> DiffNode {
-    match (left, right) {
-        (Some(l), None) => {
-            // Entire subtree removed
-            let mut children = Vec::new();
-            for c in &l.children {
-                children.push(diff_elements(Some(c), None));
-            }
-            DiffNode { status: NodeStatus::Removed, tag: l.tag.clone(), key: l.short_name.clone().or(l.uuid.clone()), children, left_xml: Some(l.serialize()), right_xml: None }
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        })
+    }
+}
+
+/// One change in a diff tree, see [`DiffNode::collect_changes`].
+#[derive(Debug, Clone, Copy)]
+pub struct Change<'a> {
+    pub node: &'a DiffNode,
+    pub kind: ChangeKind,
+    /// Key of the enclosing `FILE` node when comparing sets of documents
+    pub file: Option<&'a str>,
+}
+
+/// How the children of two matched elements are paired.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Children are aligned as sequences; a moved child shows as removed and added.
+    #[default]
+    Ordered,
+    /// Children are paired by key regardless of their position.
+    Unordered,
+}
+
+/// What identifies an element among its siblings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyRule {
+    ShortName,
+    /// The `UUID` attribute
+    Uuid,
+    /// The value of the named attribute
+    Attribute(String),
+    /// The text of the named child element, e.g. `DEFINITION-REF` for ECUC values
+    ChildText(String),
+}
+
+impl std::str::FromStr for KeyRule {
+    type Err = String;
+
+    /// `short-name`, `uuid`, `@ATTRIBUTE` or the tag of a child element.
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "" => Err("empty key rule".to_string()),
+            _ if s.eq_ignore_ascii_case("short-name") => Ok(KeyRule::ShortName),
+            _ if s.eq_ignore_ascii_case("uuid") => Ok(KeyRule::Uuid),
+            _ => match s.strip_prefix('@') {
+                Some("") => Err("missing attribute name after @".to_string()),
+                Some(attr) => Ok(KeyRule::Attribute(attr.to_string())),
+                None => Ok(KeyRule::ChildText(s.to_string())),
+            },
         }
-        (None, Some(r)) => {
-            // Entire subtree added
-            let mut children = Vec::new();
-            for c in &r.children {
-                children.push(diff_elements(None, Some(c)));
+    }
+}
+
+/// Value normalizations applied to text and attribute values before comparing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// Treat any run of whitespace as a single space
+    pub collapse_whitespace: bool,
+    /// Compare case-insensitively
+    pub ignore_case: bool,
+    /// Compare numbers by value: `0x10`, `16` and `16.0` are equal
+    pub numbers: bool,
+}
+
+impl Normalization {
+    fn apply(&self, value: &str) -> String {
+        let mut v = if self.collapse_whitespace {
+            value.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            value.to_string()
+        };
+        if self.numbers {
+            if let Some(n) = parse_number(v.trim()) { v = n; }
+        }
+        if self.ignore_case { v = v.to_lowercase(); }
+        v
+    }
+}
+
+/// Canonical form of a decimal, hexadecimal, octal or binary AUTOSAR numerical value.
+/// Integers are compared exactly, as large IDs and masks are not representable as
+/// `f64`; only values with a fraction or exponent are compared as floating point.
+fn parse_number(s: &str) -> Option<String> {
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let radix = [("0x", 16), ("0X", 16), ("0b", 2), ("0B", 2)].iter()
+        .find_map(|(p, r)| digits.strip_prefix(p).map(|d| (d, *r)))
+        .or_else(|| (digits.len() > 1 && digits.starts_with('0') && digits.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| (&digits[1..], 8)))
+        .or_else(|| digits.bytes().all(|b| b.is_ascii_digit()).then_some((digits, 10)));
+    if let Some((d, r)) = radix {
+        // sign and prefix are split off already: a sign after them is not a number
+        if d.starts_with(['+', '-']) {
+            return None;
+        }
+        if let Ok(value) = i128::from_str_radix(d, r) {
+            return Some(format!("{}", if neg { -value } else { value }));
+        }
+        if r != 10 {
+            return None;
+        }
+    }
+    if !digits.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'-' | b'+')) {
+        return None;
+    }
+    let value = digits.parse::<f64>().ok()?;
+    Some(format!("{}", if neg { -value } else { value }))
+}
+
+/// An attribute or content value that differs between the two sides of an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueChange {
+    /// `@NAME` for an attribute, `short-name` for a renamed element, otherwise `text`,
+    /// `cdata`, `comment` or `pi`
+    pub what: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Number of changes in a diff and of the severities the policy gave them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Changes, counted as in `stats`: an added or removed subtree counts once;
+    /// changed counts the innermost changed elements, not their ancestors.
+    pub counts: Counts,
+    pub info: usize,
+    pub warning: usize,
+    pub error: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.counts)?;
+        if self.info + self.warning + self.error > 0 {
+            write!(f, " ({} error, {} warning, {} info)", self.error, self.warning, self.info)?;
+        }
+        Ok(())
+    }
+}
+
+impl DiffNode {
+    pub fn has_changes(&self) -> bool {
+        self.status != NodeStatus::Unchanged
+    }
+
+    /// Whether the node is an element with a SHORT-NAME, i.e. its AUTOSAR path ends
+    /// in its own name rather than in that of an ancestor.
+    pub fn is_identifiable(&self) -> bool {
+        self.key.is_some() && self.path.rsplit('/').next() == self.key.as_deref()
+    }
+
+    /// The removed and the added element of a node that stands for two different
+    /// elements in the same place, shown as `L -> R`.
+    pub fn replaced(&self) -> Option<(&DiffNode, &DiffNode)> {
+        match self.children.as_slice() {
+            [l, r] if self.key.is_none() && l.status == NodeStatus::Removed && r.status == NodeStatus::Added
+                && self.tag == format!("{} -> {}", l.tag, r.tag) => Some((l, r)),
+            _ => None,
+        }
+    }
+
+    /// New SHORT-NAME of a renamed element.
+    pub fn renamed_to(&self) -> Option<&str> {
+        self.changes.iter().find(|c| c.what == "short-name").and_then(|c| c.right.as_deref())
+    }
+
+    /// The change this node stands for, given the status of its parent. An added or
+    /// removed subtree is a single change at its root; a changed element is a change
+    /// only if its own values changed.
+    pub fn change_kind(&self, parent: Option<NodeStatus>) -> Option<ChangeKind> {
+        match self.status {
+            NodeStatus::Added if parent != Some(NodeStatus::Added) => Some(ChangeKind::Added),
+            NodeStatus::Removed if parent != Some(NodeStatus::Removed) => Some(ChangeKind::Removed),
+            NodeStatus::Changed if !self.changes.is_empty() => Some(ChangeKind::Changed),
+            _ => None,
+        }
+    }
+
+    /// All changes in the tree, in document order.
+    pub fn collect_changes(&self) -> Vec<Change<'_>> {
+        fn rec<'a>(node: &'a DiffNode, parent: Option<NodeStatus>, file: Option<&'a str>, out: &mut Vec<Change<'a>>) {
+            let file = if node.tag == "FILE" && parent.is_some() { node.key.as_deref() } else { file };
+            if let Some(kind) = node.change_kind(parent) {
+                out.push(Change { node, kind, file });
+            }
+            for c in &node.children {
+                rec(c, Some(node.status), file, out);
             }
-            DiffNode { status: NodeStatus::Added, tag: r.tag.clone(), key: r.short_name.clone().or(r.uuid.clone()), children, left_xml: None, right_xml: Some(r.serialize()) }
         }
-        (Some(l), Some(r)) => {
-            let key_l = key_of(l);
-            let key_r = key_of(r);
-            if key_l != key_r || l.tag != r.tag {
-                // Different nodes in same position: represent as removed + added
-                return DiffNode {
-                    status: NodeStatus::Changed,
-                    tag: format!("{} -> {}", l.tag, r.tag),
-                    key: None,
-                    children: vec![diff_elements(Some(l), None), diff_elements(None, Some(r))],
-                    left_xml: Some(l.serialize()),
-                    right_xml: Some(r.serialize()),
-                };
+        let mut out = Vec::new();
+        rec(self, None, None, &mut out);
+        out
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary { counts: Counts::of(self), ..Summary::default() };
+        self.count_severities(&mut summary);
+        summary
+    }
+
+    /// Highest severity of any change in the subtree.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.children.iter().map(DiffNode::max_severity).fold(self.severity, Ord::max)
+    }
+
+    fn count_severities(&self, summary: &mut Summary) {
+        match self.severity {
+            Some(Severity::Info) => summary.info += 1,
+            Some(Severity::Warning) => summary.warning += 1,
+            Some(Severity::Error) => summary.error += 1,
+            None => {}
+        }
+        if self.status == NodeStatus::Changed {
+            for c in &self.children {
+                c.count_severities(summary);
             }
+        }
+    }
+}
 
-            // Same key/tag: compare attributes/text and children
-            let mut node = DiffNode { status: NodeStatus::Unchanged, tag: l.tag.clone(), key: l.short_name.clone().or(l.uuid.clone()), children: Vec::new(), left_xml: Some(l.serialize()), right_xml: Some(r.serialize()) };
+/// Options controlling what the diff compares. Build them with [`DiffOptions::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    include_comments: bool,
+    matching: MatchMode,
+    ignored_tags: Vec<String>,
+    ignored_attributes: Vec<String>,
+    key_rules: Vec<(String, KeyRule)>,
+    normalization: Normalization,
+}
+
+impl DiffOptions {
+    pub fn builder() -> DiffOptionsBuilder {
+        DiffOptionsBuilder::default()
+    }
+
+    pub(crate) fn is_ignored(&self, e: &Element) -> bool {
+        self.ignored_tags.contains(&e.tag)
+    }
+
+    /// Children that take part in the comparison.
+    fn children<'a>(&self, e: &'a Element) -> Vec<&'a Element> {
+        e.children.iter().filter(|c| !self.is_ignored(c)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptionsBuilder {
+    opts: DiffOptions,
+}
+
+impl DiffOptionsBuilder {
+    /// Also compare comments, processing instructions and CDATA markup.
+    /// When off, comments and PIs are ignored and CDATA compares equal to plain text.
+    pub fn include_comments(mut self, include: bool) -> Self {
+        self.opts.include_comments = include;
+        self
+    }
+
+    pub fn matching(mut self, mode: MatchMode) -> Self {
+        self.opts.matching = mode;
+        self
+    }
+
+    /// Leave elements with this tag, and everything below them, out of the diff.
+    pub fn ignore_tag(mut self, tag: impl Into<String>) -> Self {
+        self.opts.ignored_tags.push(tag.into());
+        self
+    }
+
+    /// Leave attributes with this local name out of the comparison.
+    pub fn ignore_attribute(mut self, name: impl Into<String>) -> Self {
+        self.opts.ignored_attributes.push(name.into());
+        self
+    }
+
+    /// Identify elements with this tag by `rule` instead of SHORT-NAME or UUID.
+    /// Elements the rule yields no value for fall back to the default.
+    pub fn key_rule(mut self, tag: impl Into<String>, rule: KeyRule) -> Self {
+        self.opts.key_rules.push((tag.into(), rule));
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.opts.normalization = normalization;
+        self
+    }
+
+    pub fn build(self) -> DiffOptions {
+        self.opts
+    }
+}
+
+fn apply_key_rule(e: &Element, rule: &KeyRule) -> Option<String> {
+    match rule {
+        KeyRule::ShortName => e.short_name.clone(),
+        KeyRule::Uuid => e.uuid.clone(),
+        KeyRule::Attribute(name) => e.attribute(name).map(str::to_string),
+        KeyRule::ChildText(tag) => e.children.iter().find(|c| c.tag == *tag).and_then(|c| c.text()),
+    }
+}
+
+/// Identity of an element among its siblings, if it has one.
+fn key_of(e: &Element, opts: &DiffOptions) -> Option<String> {
+    opts.key_rules.iter()
+        .filter(|(tag, _)| *tag == e.tag)
+        .find_map(|(_, rule)| apply_key_rule(e, rule))
+        .or_else(|| e.short_name.clone())
+        .or_else(|| e.uuid.clone())
+}
+
+/// Key used to pair siblings: elements without an identity pair up by tag.
+fn match_key(e: &Element, opts: &DiffOptions) -> String {
+    key_of(e, opts).unwrap_or_else(|| e.tag.clone())
+}
+
+/// Attribute lists are compared as sets of resolved names: source order, namespace
+/// declarations and prefix choice do not matter. `xsi:schemaLocation` is reported
+/// separately by [`schema_notes`].
+fn attribute_changes(opts: &DiffOptions, l: &Element, ls: &[(String, String)], r: &Element, rs: &[(String, String)]) -> Vec<ValueChange> {
+    let comparable = |e: &'_ Element, scope: &[(String, String)]| -> Vec<((Option<String>, String), String)> {
+        e.resolved_attributes(scope).into_iter()
+            .filter(|(ns, local, _)| !(*ns == Some(XSI_NAMESPACE) && *local == "schemaLocation"))
+            .filter(|(_, local, _)| !opts.ignored_attributes.iter().any(|a| a == local))
+            .map(|(ns, local, value)| ((ns.map(str::to_string), local.to_string()), value.to_string()))
+            .collect()
+    };
+    let (left, right) = (comparable(l, ls), comparable(r, rs));
+    let mut changes = Vec::new();
+    for (name, lv) in &left {
+        let rv = right.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+        if rv.map(|rv| opts.normalization.apply(lv) != opts.normalization.apply(rv)).unwrap_or(true) {
+            changes.push(ValueChange { what: format!("@{}", name.1), left: Some(lv.clone()), right: rv.cloned() });
+        }
+    }
+    for (name, rv) in &right {
+        if !left.iter().any(|(n, _)| n == name) {
+            changes.push(ValueChange { what: format!("@{}", name.1), left: None, right: Some(rv.clone()) });
+        }
+    }
+    changes
+}
+
+/// Changed text, CDATA, comments and PIs. Each kind is reported once, with all its
+/// items joined; a change in position only relative to the child elements is
+/// reported as a text change with equal values.
+fn content_changes(opts: &DiffOptions, l: &Element, r: &Element) -> Vec<ValueChange> {
+    let (left, right) = (comparable_content(l, opts), comparable_content(r, opts));
+    let normalized = |items: &[(usize, &'static str, String)]| -> Vec<(usize, &'static str, String)> {
+        items.iter()
+            .map(|(pos, kind, t)| (*pos, *kind, if *kind == "text" { opts.normalization.apply(t) } else { t.clone() }))
+            .collect()
+    };
+    if normalized(&left) == normalized(&right) { return Vec::new(); }
 
-            // Quick content check (attributes/text)
-            if l.text != r.text || l.attributes != r.attributes {
-                node.status = NodeStatus::Changed;
+    let joined = |items: &[(usize, &'static str, String)], kind: &str| -> Option<String> {
+        let values: Vec<&str> = items.iter().filter(|(_, k, _)| *k == kind).map(|(_, _, t)| t.as_str()).collect();
+        (!values.is_empty()).then(|| values.join(" "))
+    };
+    let mut changes = Vec::new();
+    for kind in ["text", "cdata", "comment", "pi"] {
+        let (lv, rv) = (joined(&left, kind), joined(&right, kind));
+        let differs = match (&lv, &rv) {
+            (Some(a), Some(b)) if kind == "text" => opts.normalization.apply(a) != opts.normalization.apply(b),
+            _ => lv != rv,
+        };
+        if differs {
+            changes.push(ValueChange { what: kind.to_string(), left: lv, right: rv });
+        }
+    }
+    if changes.is_empty() {
+        changes.push(ValueChange { what: "text".to_string(), left: joined(&left, "text"), right: joined(&right, "text") });
+    }
+    changes
+}
+
+/// Text, comments, CDATA and PIs of an element in a comparable form: adjacent text is
+/// merged and trimmed, and each item keeps its position relative to the child elements.
+fn comparable_content(e: &Element, opts: &DiffOptions) -> Vec<(usize, &'static str, String)> {
+    let mut out: Vec<(usize, &'static str, String)> = Vec::new();
+    for c in &e.content {
+        let (kind, t) = match &c.node {
+            ContentNode::Text(t) => ("text", t),
+            ContentNode::CData(t) if !opts.include_comments => ("text", t),
+            ContentNode::CData(t) => ("cdata", t),
+            ContentNode::Comment(t) if opts.include_comments => ("comment", t),
+            ContentNode::ProcessingInstruction(t) if opts.include_comments => ("pi", t),
+            _ => continue,
+        };
+        match out.last_mut() {
+            Some((pos, "text", prev)) if kind == "text" && *pos == c.pos => prev.push_str(t),
+            _ => out.push((c.pos, kind, t.clone())),
+        }
+    }
+    for item in &mut out {
+        item.2 = item.2.trim().to_string();
+    }
+    out.retain(|(_, kind, t)| *kind != "text" || !t.is_empty());
+    out
+}
+
+/// AUTOSAR schema file named in a schema location, e.g. `AUTOSAR_00051`.
+fn schema_version(location: &str) -> Option<&str> {
+    let file = location.split_whitespace().last()?;
+    let file = file.rsplit('/').next().unwrap_or(file);
+    Some(file.strip_suffix(".xsd").unwrap_or(file))
+}
+
+/// [`schema_notes`] plus differences in the input encoding of the two documents.
+pub fn document_notes(left: &Document, right: &Document) -> Vec<String> {
+    let mut notes = Vec::new();
+    if left.encoding != right.encoding {
+        notes.push(format!("input encoding differs: {} vs {}", left.encoding, right.encoding));
+    }
+    notes.extend(schema_notes(&left.root, &right.root));
+    notes
+}
+
+/// Top-level notes about document differences that are not part of the structural
+/// diff: a different root namespace, AUTOSAR schema version or `xsi:schemaLocation`.
+pub fn schema_notes(left: &Element, right: &Element) -> Vec<String> {
+    let mut notes = Vec::new();
+    if left.namespace != right.namespace {
+        notes.push(format!(
+            "root namespace differs: {} vs {}",
+            left.namespace.as_deref().unwrap_or("<none>"),
+            right.namespace.as_deref().unwrap_or("<none>"),
+        ));
+    }
+    let ls = left.child_scope(&[]);
+    let rs = right.child_scope(&[]);
+    let (l_loc, r_loc) = (left.schema_location(&ls), right.schema_location(&rs));
+    if l_loc != r_loc {
+        match (l_loc.and_then(schema_version), r_loc.and_then(schema_version)) {
+            (Some(lv), Some(rv)) if lv != rv => {
+                notes.push(format!("AUTOSAR schema version differs: {} vs {}", lv, rv));
             }
+            _ => notes.push(format!(
+                "xsi:schemaLocation differs: \"{}\" vs \"{}\"",
+                l_loc.unwrap_or(""),
+                r_loc.unwrap_or(""),
+            )),
+        }
+    }
+    notes
+}
+
+/// An element together with the namespace declarations inherited from its ancestors,
+/// so serialized fragments stay well-formed on their own.
+type Side<'a> = Option<(&'a Element, &'a [(String, String)])>;
 
-            // Diff children by keys using similar
-            let left_keys: Vec<String> = l.children.iter().map(|c| key_of(c)).collect();
-            let right_keys: Vec<String> = r.children.iter().map(|c| key_of(c)).collect();
+/// Indices of paired children: both for a matched pair, one for a removed or added child.
+pub(crate) type Pairs = Vec<(Option<usize>, Option<usize>)>;
+
+/// Whether `r` is `l` under a new SHORT-NAME, i.e. both carry the same UUID.
+fn is_rename(l: &Element, r: &Element) -> bool {
+    l.same_name(r) && l.uuid.is_some() && l.uuid == r.uuid
+        && l.short_name.is_some() && r.short_name.is_some() && l.short_name != r.short_name
+}
 
-            let ops = capture_diff_slices(Algorithm::Myers, &left_keys, &right_keys);
+/// Pair a removed and an added sibling that are the same element under a new
+/// SHORT-NAME, at the position of the removed one.
+fn pair_renames(pairs: &mut Pairs, left: &[&Element], right: &[&Element]) {
+    // Added children by UUID, as indices into `pairs` in output order
+    let mut added: std::collections::HashMap<&str, Vec<usize>> = Default::default();
+    for (j, p) in pairs.iter().enumerate() {
+        if let (None, Some(uuid)) = (p.0, p.1.and_then(|ri| right[ri].uuid.as_deref())) {
+            added.entry(uuid).or_default().push(j);
+        }
+    }
+    if added.is_empty() {
+        return;
+    }
+    let mut taken = vec![false; pairs.len()];
+    let mut renamed = vec![None; pairs.len()];
+    for (i, p) in pairs.iter().enumerate() {
+        let (Some(li), None) = *p else { continue };
+        let Some(candidates) = left[li].uuid.as_deref().and_then(|u| added.get(u)) else { continue };
+        let found = candidates.iter().copied()
+            .find(|&j| !taken[j] && pairs[j].1.is_some_and(|ri| is_rename(left[li], right[ri])));
+        if let Some(j) = found {
+            taken[j] = true;
+            renamed[i] = pairs[j].1;
+        }
+    }
+    *pairs = pairs.iter().zip(renamed).zip(&taken)
+        .filter(|(_, taken)| !**taken)
+        .map(|((p, ri), _)| if ri.is_some() { (p.0, ri) } else { *p })
+        .collect();
+}
 
-            for op in ops.iter() {
+/// Pair up the children of two matched elements, in output order.
+fn pair_children(left_keys: &[String], right_keys: &[String], mode: MatchMode) -> Pairs {
+    let mut pairs = Vec::new();
+    match mode {
+        MatchMode::Ordered => {
+            for op in capture_diff_slices(Algorithm::Myers, left_keys, right_keys) {
                 use similar::DiffOp as SOp;
                 match op {
                     SOp::Equal { old_index, new_index, len } => {
-                        for k in 0..*len {
-                            let li = old_index + k;
-                            let ri = new_index + k;
-                            let child = diff_elements(Some(&l.children[li]), Some(&r.children[ri]));
-                            if child.status != NodeStatus::Unchanged { node.status = NodeStatus::Changed; }
-                            node.children.push(child);
-                        }
+                        pairs.extend((0..len).map(|k| (Some(old_index + k), Some(new_index + k))));
                     }
                     SOp::Delete { old_index, old_len, .. } => {
-                        for k in 0..*old_len {
-                            let li = old_index + k;
-                            let child = diff_elements(Some(&l.children[li]), None);
-                            node.status = NodeStatus::Changed;
-                            node.children.push(child);
-                        }
+                        pairs.extend((old_index..old_index + old_len).map(|li| (Some(li), None)));
                     }
                     SOp::Insert { new_index, new_len, .. } => {
-                        for k in 0..*new_len {
-                            let ri = new_index + k;
-                            let child = diff_elements(None, Some(&r.children[ri]));
-                            node.status = NodeStatus::Changed;
-                            node.children.push(child);
-                        }
+                        pairs.extend((new_index..new_index + new_len).map(|ri| (None, Some(ri))));
                     }
                     SOp::Replace { old_index, old_len, new_index, new_len } => {
-                        for k in 0..*old_len {
-                            let li = old_index + k;
-                            let child = diff_elements(Some(&l.children[li]), None);
-                            node.status = NodeStatus::Changed;
-                            node.children.push(child);
-                        }
-                        for k in 0..*new_len {
-                            let ri = new_index + k;
-                            let child = diff_elements(None, Some(&r.children[ri]));
-                            node.status = NodeStatus::Changed;
-                            node.children.push(child);
-                        }
+                        pairs.extend((old_index..old_index + old_len).map(|li| (Some(li), None)));
+                        pairs.extend((new_index..new_index + new_len).map(|ri| (None, Some(ri))));
                     }
                 }
             }
+        }
+        MatchMode::Unordered => {
+            // Duplicate keys pair up in source order
+            let mut unmatched: std::collections::HashMap<&str, std::collections::VecDeque<usize>> = Default::default();
+            for (li, k) in left_keys.iter().enumerate() {
+                unmatched.entry(k.as_str()).or_default().push_back(li);
+            }
+            let matches: Vec<Option<usize>> = right_keys.iter()
+                .map(|k| unmatched.get_mut(k.as_str()).and_then(|q| q.pop_front()))
+                .collect();
+            let mut matched_left = vec![false; left_keys.len()];
+            for li in matches.iter().flatten() { matched_left[*li] = true; }
+
+            // Follow the right-hand order; removed children follow their left-hand
+            // predecessor wherever that ends up
+            let removed_after = |pairs: &mut Vec<_>, from: usize| {
+                pairs.extend((from..left_keys.len()).take_while(|li| !matched_left[*li]).map(|li| (Some(li), None)));
+            };
+            removed_after(&mut pairs, 0);
+            for (ri, m) in matches.iter().enumerate() {
+                pairs.push((*m, Some(ri)));
+                if let Some(li) = *m { removed_after(&mut pairs, li + 1); }
+            }
+        }
+    }
+    pairs
+}
+
+/// AUTOSAR path of `e` below an element with path `parent`.
+fn element_path(parent: &str, e: &Element) -> String {
+    match &e.short_name {
+        Some(sn) => format!("{}/{}", parent, sn),
+        None => parent.to_string(),
+    }
+}
+
+/// Whether `l` and `r`, found at the same place, are different elements rather than
+/// two versions of one. The diff shows them as a `L -> R` node with the removed and
+/// the added element below it.
+pub(crate) fn is_mismatch(opts: &DiffOptions, l: &Element, r: &Element) -> bool {
+    (match_key(l, opts) != match_key(r, opts) && !is_rename(l, r)) || !l.same_name(r)
+}
+
+/// The compared children of two matched elements and how they pair up, in the
+/// order of the children of their diff node.
+pub(crate) fn paired_children<'a>(opts: &DiffOptions, l: &'a Element, r: &'a Element) -> (Vec<&'a Element>, Vec<&'a Element>, Pairs) {
+    let l_children = opts.children(l);
+    let r_children = opts.children(r);
+    let left_keys: Vec<String> = l_children.iter().map(|c| match_key(c, opts)).collect();
+    let right_keys: Vec<String> = r_children.iter().map(|c| match_key(c, opts)).collect();
+
+    let mut pairs = pair_children(&left_keys, &right_keys, opts.matching);
+    pair_renames(&mut pairs, &l_children, &r_children);
+    (l_children, r_children, pairs)
+}
+
+fn diff_elements(opts: &DiffOptions, parent_path: &str, left: Side, right: Side) -> DiffNode {
+    match (left, right) {
+        (Some((l, ls)), None) => {
+            // Entire subtree removed
+            let scope = l.child_scope(ls);
+            let path = element_path(parent_path, l);
+            let children = opts.children(l).into_iter()
+                .map(|c| diff_elements(opts, &path, Some((c, &scope)), None))
+                .collect();
+            DiffNode { status: NodeStatus::Removed, tag: l.tag.clone(), key: key_of(l, opts), path, changes: Vec::new(), severity: None, children, left_xml: Some(l.serialize_in_scope(ls)), right_xml: None, left_pos: l.position, right_pos: None, left_uuid: l.uuid.clone(), right_uuid: None }
+        }
+        (None, Some((r, rs))) => {
+            // Entire subtree added
+            let scope = r.child_scope(rs);
+            let path = element_path(parent_path, r);
+            let children = opts.children(r).into_iter()
+                .map(|c| diff_elements(opts, &path, None, Some((c, &scope))))
+                .collect();
+            DiffNode { status: NodeStatus::Added, tag: r.tag.clone(), key: key_of(r, opts), path, changes: Vec::new(), severity: None, children, left_xml: None, right_xml: Some(r.serialize_in_scope(rs)), left_pos: None, right_pos: r.position, left_uuid: None, right_uuid: r.uuid.clone() }
+        }
+        (Some((l, ls)), Some((r, rs))) => {
+            if is_mismatch(opts, l, r) {
+                // Different nodes in same position: represent as removed + added
+                return DiffNode {
+                    status: NodeStatus::Changed,
+                    tag: format!("{} -> {}", l.tag, r.tag),
+                    key: None,
+                    path: parent_path.to_string(),
+                    changes: Vec::new(),
+                    severity: None,
+                    children: vec![diff_elements(opts, parent_path, Some((l, ls)), None), diff_elements(opts, parent_path, None, Some((r, rs)))],
+                    left_xml: Some(l.serialize_in_scope(ls)),
+                    right_xml: Some(r.serialize_in_scope(rs)),
+                    left_pos: l.position,
+                    right_pos: r.position,
+                    left_uuid: l.uuid.clone(),
+                    right_uuid: r.uuid.clone(),
+                };
+            }
+
+            // Same key/tag: compare attributes/text and children
+            let path = element_path(parent_path, l);
+            let l_scope = l.child_scope(ls);
+            let r_scope = r.child_scope(rs);
+            let mut changes = Vec::new();
+            if l.short_name != r.short_name {
+                changes.push(ValueChange { what: "short-name".to_string(), left: l.short_name.clone(), right: r.short_name.clone() });
+            }
+            changes.extend(attribute_changes(opts, l, &l_scope, r, &r_scope));
+            changes.extend(content_changes(opts, l, r));
+            let status = if changes.is_empty() { NodeStatus::Unchanged } else { NodeStatus::Changed };
+            let mut node = DiffNode { status, tag: l.tag.clone(), key: key_of(l, opts), path, changes, severity: None, children: Vec::new(), left_xml: Some(l.serialize_in_scope(ls)), right_xml: Some(r.serialize_in_scope(rs)), left_pos: l.position, right_pos: r.position, left_uuid: l.uuid.clone(), right_uuid: r.uuid.clone() };
+
+            // Diff children by keys
+            let (l_children, r_children, pairs) = paired_children(opts, l, r);
+            for (li, ri) in pairs {
+                let child = diff_elements(
+                    opts,
+                    &node.path,
+                    li.map(|i| (l_children[i], &l_scope[..])),
+                    ri.map(|i| (r_children[i], &r_scope[..])),
+                );
+                if child.status != NodeStatus::Unchanged { node.status = NodeStatus::Changed; }
+                node.children.push(child);
+            }
 
             node
         }
@@ -126,5 +699,35 @@ fn diff_elements(left: Option<&Element>, right: Option<&Element>) -> DiffNode {
 }
 
 pub fn compute_tree_diff(left: &Element, right: &Element) -> DiffNode {
-    diff_elements(Some(left), Some(right))
+    compute_tree_diff_with(left, right, &DiffOptions::default())
+}
+
+pub fn compute_tree_diff_with(left: &Element, right: &Element, opts: &DiffOptions) -> DiffNode {
+    diff_elements(opts, "", Some((left, &[])), Some((right, &[])))
+}
+
+/// Diff two sets of documents (e.g. directories or archives) paired by relative path.
+/// Both sides must be sorted by path. Each file becomes a `FILE` node keyed by its
+/// path, below a synthetic `FILES` root.
+pub fn compute_multi_diff(left: &[(String, Element)], right: &[(String, Element)], opts: &DiffOptions) -> DiffNode {
+    let mut root = DiffNode { status: NodeStatus::Unchanged, tag: "FILES".to_string(), key: None, path: String::new(), changes: Vec::new(), severity: None, children: Vec::new(), left_xml: None, right_xml: None, left_pos: None, right_pos: None, left_uuid: None, right_uuid: None };
+    let (mut li, mut ri) = (0, 0);
+    while li < left.len() || ri < right.len() {
+        let (path, l, r) = match (left.get(li), right.get(ri)) {
+            (Some((lp, l)), Some((rp, r))) if lp == rp => { li += 1; ri += 1; (lp, Some(l), Some(r)) }
+            (Some((lp, l)), Some((rp, _))) if lp < rp => { li += 1; (lp, Some(l), None) }
+            (Some((lp, l)), None) => { li += 1; (lp, Some(l), None) }
+            (_, Some((rp, r))) => { ri += 1; (rp, None, Some(r)) }
+            (None, None) => unreachable!(),
+        };
+        let child = diff_elements(opts, "", l.map(|e| (e, &[][..])), r.map(|e| (e, &[][..])));
+        let status = match (l, r) {
+            (Some(_), None) => NodeStatus::Removed,
+            (None, Some(_)) => NodeStatus::Added,
+            _ => if child.status == NodeStatus::Unchanged { NodeStatus::Unchanged } else { NodeStatus::Changed },
+        };
+        if status != NodeStatus::Unchanged { root.status = NodeStatus::Changed; }
+        root.children.push(DiffNode { status, tag: "FILE".to_string(), key: Some(path.clone()), path: String::new(), changes: Vec::new(), severity: None, children: vec![child], left_xml: None, right_xml: None, left_pos: None, right_pos: None, left_uuid: None, right_uuid: None });
+    }
+    root
 }
@@ -0,0 +1,51 @@
+use crate::parse::Diagnostic;
+use std::fmt;
+
+/// Errors returned by the library API.
+#[derive(Debug)]
+pub enum Error {
+    /// An input could not be read
+    Io { path: String, source: std::io::Error },
+    /// An input could not be decompressed or unpacked, or holds no ARXML
+    Input { path: String, message: String },
+    /// A document is not well-formed XML
+    Parse(Diagnostic),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn io(path: &str, source: std::io::Error) -> Self {
+        Error::Io { path: path.to_string(), source }
+    }
+
+    pub(crate) fn input(path: &str, message: impl fmt::Display) -> Self {
+        Error::Input { path: path.to_string(), message: message.to_string() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "cannot read {}: {}", path, source),
+            Error::Input { path, message } => write!(f, "{}: {}", path, message),
+            Error::Parse(d) => d.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Input { .. } => None,
+            Error::Parse(d) => Some(d),
+        }
+    }
+}
+
+impl From<Diagnostic> for Error {
+    fn from(d: Diagnostic) -> Self {
+        Error::Parse(d)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const ZIP_MAGIC: &[u8] = &[0x50, 0x4b, 0x03, 0x04];
+
+/// Separates an archive from an entry inside it: `bundle.zip!/path/System.arxml`
+const ARCHIVE_ENTRY_SEPARATOR: &str = "!/";
+
+/// File name suffixes picked up when comparing directories or archives.
+/// Compression suffixes are dropped from the relative path used for pairing.
+const MEMBER_SUFFIXES: &[&str] = &[".arxml", ".arxml.gz", ".arxml.zst"];
+
+/// Split `archive.zip!/entry` into the archive path and the entry name.
+fn split_archive_entry(spec: &str) -> Option<(&str, &str)> {
+    let idx = spec.find(ARCHIVE_ENTRY_SEPARATOR)?;
+    Some((&spec[..idx], &spec[idx + ARCHIVE_ENTRY_SEPARATOR.len()..]))
+}
+
+/// Transparently decompress gzip and zstd data, detected by their magic bytes.
+pub(crate) fn decompress(data: Vec<u8>, name: &str) -> Result<Vec<u8>> {
+    if data.starts_with(GZIP_MAGIC) {
+        let mut out = Vec::new();
+        flate2::read::MultiGzDecoder::new(&data[..]).read_to_end(&mut out)
+            .map_err(|e| Error::input(name, format_args!("cannot decompress gzip data: {}", e)))?;
+        return Ok(out);
+    }
+    if data.starts_with(ZSTD_MAGIC) {
+        return zstd::decode_all(&data[..])
+            .map_err(|e| Error::input(name, format_args!("cannot decompress zstd data: {}", e)));
+    }
+    Ok(data)
+}
+
+fn read_archive_entry(archive: &str, entry: &str) -> Result<Vec<u8>> {
+    let file = File::open(archive).map_err(|e| Error::io(archive, e))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| Error::input(archive, format_args!("not a zip archive: {}", e)))?;
+    let mut f = zip.by_name(entry).map_err(|_| Error::input(archive, format_args!("no entry {}", entry)))?;
+    let mut data = Vec::new();
+    f.read_to_end(&mut data).map_err(|e| Error::io(&format!("{}{}{}", archive, ARCHIVE_ENTRY_SEPARATOR, entry), e))?;
+    Ok(data)
+}
+
+/// Read a single input: a plain, gzip- or zstd-compressed file, or an entry inside
+/// a zip archive (`bundle.zip!/path/System.arxml`).
+pub fn read_input(spec: &str) -> Result<Vec<u8>> {
+    let data = match split_archive_entry(spec) {
+        Some((archive, entry)) => read_archive_entry(archive, entry)?,
+        None => std::fs::read(spec).map_err(|e| Error::io(spec, e))?,
+    };
+    decompress(data, spec)
+}
+
+/// Whether `spec` names a set of documents (a directory or a zip archive)
+/// rather than a single document.
+pub fn is_collection(spec: &str) -> bool {
+    if split_archive_entry(spec).is_some() { return false; }
+    let path = Path::new(spec);
+    if path.is_dir() { return true; }
+    let mut magic = [0u8; 4];
+    File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && magic == ZIP_MAGIC
+}
+
+/// Relative path used to pair members of two collections, or `None` if the file is not ARXML.
+fn member_key(name: &str) -> Option<String> {
+    let lower = name.to_ascii_lowercase();
+    let suffix = MEMBER_SUFFIXES.iter().find(|s| lower.ends_with(*s))?;
+    let keep = name.len() - suffix.len() + ".arxml".len();
+    Some(name[..keep].replace('\\', "/"))
+}
+
+/// Input spec of the member `key` of the directory or archive `spec`, for reports.
+/// A compression suffix dropped from the key is not restored.
+pub fn member_spec(spec: &str, key: &str) -> String {
+    if Path::new(spec).is_dir() {
+        Path::new(spec).join(key).to_string_lossy().into_owned()
+    } else {
+        format!("{}{}{}", spec, ARCHIVE_ENTRY_SEPARATOR, key)
+    }
+}
+
+/// One ARXML document of a directory or archive.
+pub struct Member {
+    /// Path relative to the directory or archive root, without compression suffix
+    pub key: String,
+    /// Full input spec, e.g. `dir/System.arxml.gz` or `bundle.zip!/System.arxml`
+    pub spec: String,
+    pub data: Vec<u8>,
+}
+
+/// Read all ARXML documents of a directory (recursively) or zip archive,
+/// sorted by relative path.
+pub fn read_collection(spec: &str) -> Result<Vec<Member>> {
+    let mut members = Vec::new();
+    let path = Path::new(spec);
+    if path.is_dir() {
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let dir_name = dir.to_string_lossy().into_owned();
+            let entries = std::fs::read_dir(&dir).map_err(|e| Error::io(&dir_name, e))?;
+            for entry in entries {
+                let p = entry.map_err(|e| Error::io(&dir_name, e))?.path();
+                if p.is_dir() {
+                    pending.push(p);
+                    continue;
+                }
+                let rel = p.strip_prefix(path).unwrap_or(&p).to_string_lossy().into_owned();
+                if let Some(key) = member_key(&rel) {
+                    let spec = p.to_string_lossy().into_owned();
+                    let data = read_input(&spec)?;
+                    members.push(Member { key, spec, data });
+                }
+            }
+        }
+    } else {
+        let data = std::fs::read(spec).map_err(|e| Error::io(spec, e))?;
+        let mut zip = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| Error::input(spec, format_args!("not a zip archive: {}", e)))?;
+        for i in 0..zip.len() {
+            let mut f = zip.by_index(i).map_err(|e| Error::input(spec, e))?;
+            if f.is_dir() { continue; }
+            let name = f.name().to_string();
+            let Some(key) = member_key(&name) else { continue };
+            let entry_spec = format!("{}{}{}", spec, ARCHIVE_ENTRY_SEPARATOR, name);
+            let mut data = Vec::new();
+            f.read_to_end(&mut data).map_err(|e| Error::io(&entry_spec, e))?;
+            let data = decompress(data, &entry_spec)?;
+            members.push(Member { key, spec: entry_spec, data });
+        }
+    }
+    if members.is_empty() {
+        return Err(Error::input(spec, "no ARXML files found"));
+    }
+    members.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(members)
+}
@@ -1,28 +1,204 @@
 pub mod cli;
+pub mod error;
+pub mod input;
+pub mod merge;
 pub mod parse;
 pub mod diff;
+pub mod policy;
 pub mod render;
+pub mod report;
+pub mod review;
+pub mod stats;
 pub mod tui;
 
 use anyhow::Result;
-use crate::cli::Args;
+use crate::cli::{Args, Format, CompareArgs, StatsArgs, View};
+use std::io::Write;
+use std::time::{Duration, Instant};
 
-pub fn run(args: Args) -> Result<()> {
-    let left = parse::parse_file(&args.left)?;
-    let right = parse::parse_file(&args.right)?;
+/// A rated diff of two inputs, with what was learnt on the way.
+struct Comparison {
+    root: diff::DiffNode,
+    notes: Vec<String>,
+    warnings: Vec<parse::Diagnostic>,
+    /// Elements in the left and right inputs
+    elements: (usize, usize),
+    /// The parsed inputs, unless they are sets of documents
+    documents: Option<(parse::Document, parse::Document)>,
+    parse_time: Duration,
+    diff_time: Duration,
+}
+
+impl Comparison {
+    fn stats(&self) -> stats::Stats {
+        stats::Stats {
+            left_elements: self.elements.0,
+            right_elements: self.elements.1,
+            parse_time: self.parse_time,
+            diff_time: self.diff_time,
+            ..stats::Stats::of(&self.root)
+        }
+    }
+}
+
+/// Parse both inputs, diff them and rate the changes with the policy. Directories and
+/// zip archives are compared as sets of documents paired by relative path.
+fn compare(left: &str, right: &str, args: &CompareArgs) -> Result<Comparison> {
+    let parse_opts = parse::ParseOptions { lenient: args.lenient };
+    let opts = args.diff_options();
+    let policy = match &args.policy {
+        Some(path) => policy::Policy::load(path)?,
+        None => policy::Policy::autosar(),
+    };
 
-    let diff_root = diff::compute_tree_diff(&left, &right);
+    let started = Instant::now();
+    let mut comparison = match (input::is_collection(left), input::is_collection(right)) {
+        (false, false) => {
+            let mut left_doc = parse::parse_document_with(left, &parse_opts)?;
+            let mut right_doc = parse::parse_document_with(right, &parse_opts)?;
+            let parse_time = started.elapsed();
+            let root = diff::compute_tree_diff_with(&left_doc.root, &right_doc.root, &opts);
+            let diff_time = started.elapsed() - parse_time;
+            let warnings = std::mem::take(&mut left_doc.warnings).into_iter().chain(std::mem::take(&mut right_doc.warnings)).collect();
+            Comparison {
+                root,
+                notes: diff::document_notes(&left_doc, &right_doc),
+                elements: (left_doc.root.element_count(), right_doc.root.element_count()),
+                documents: Some((left_doc, right_doc)),
+                warnings,
+                parse_time,
+                diff_time,
+            }
+        }
+        (true, true) => {
+            let mut warnings = Vec::new();
+            let mut parse_all = |spec: &str| -> Result<Vec<(String, parse::Document)>> {
+                let mut docs = Vec::new();
+                for m in input::read_collection(spec)? {
+                    let mut doc = parse::parse_bytes(&m.data, &m.spec, &parse_opts)?;
+                    warnings.append(&mut doc.warnings);
+                    docs.push((m.key, doc));
+                }
+                Ok(docs)
+            };
+            let left_docs = parse_all(left)?;
+            let right_docs = parse_all(right)?;
+            let parse_time = started.elapsed();
+
+            let mut notes = Vec::new();
+            for (key, l) in &left_docs {
+                if let Some((_, r)) = right_docs.iter().find(|(k, _)| k == key) {
+                    notes.extend(diff::document_notes(l, r).into_iter().map(|n| format!("{}: {}", key, n)));
+                }
+            }
+            let count = |docs: &[(String, parse::Document)]| docs.iter().map(|(_, d)| d.root.element_count()).sum();
+            let elements = (count(&left_docs), count(&right_docs));
+            let roots = |docs: Vec<(String, parse::Document)>| docs.into_iter().map(|(k, d)| (k, d.root)).collect::<Vec<_>>();
+            let root = diff::compute_multi_diff(&roots(left_docs), &roots(right_docs), &opts);
+            Comparison { root, notes, warnings, elements, documents: None, parse_time, diff_time: started.elapsed() - parse_time }
+        }
+        _ => anyhow::bail!("cannot compare a directory or archive with a single file"),
+    };
+    policy.apply(&mut comparison.root);
+    Ok(comparison)
+}
+
+/// Run the comparison described by `args`. Returns whether differences were found,
+/// counting only those of at least the `--fail-on` severity if given.
+pub fn run(args: Args) -> Result<bool> {
+    let comparison = compare(&args.left, &args.right, &args.compare)?;
+    let Comparison { root: diff_root, notes, warnings, documents, .. } = &comparison;
+    let differ = match args.fail_on {
+        Some(threshold) => diff_root.max_severity() >= Some(threshold),
+        None => diff_root.has_changes(),
+    };
+
+    if args.interactive || args.review.is_some() || args.merge.is_some() {
+        // notes and the first line of each warning are shown below the tree
+        let mut messages: Vec<String> = notes.iter().map(|n| format!("note: {}", n)).collect();
+        messages.extend(warnings.iter().map(|w| format!("warning: {}", w.to_string().lines().next().unwrap_or(""))));
+
+        let mut app = tui::App::new(diff_root, &messages);
+        if let Some(file) = &args.review {
+            let file = file.clone().unwrap_or_else(|| review::default_review_file(&args.right));
+            app = app.with_review(review::Review::open(file, &args.left, &args.right)?);
+        }
+        if let Some(output) = &args.merge {
+            let Some((left_doc, right_doc)) = documents else {
+                anyhow::bail!("--merge needs two ARXML files, not directories or archives");
+            };
+            let output = output.clone().unwrap_or_else(|| merge::default_merge_output(&args.right));
+            app = app.with_merge(left_doc, right_doc, args.compare.diff_options(), output);
+        }
 
-    if args.interactive {
         // launch the interactive TUI
-        crate::tui::run_tui(&diff_root)?;
-        return Ok(())
+        tui::run_tui(app)?;
+        return Ok(differ)
+    }
+
+    if args.quiet {
+        return Ok(differ);
+    }
+
+    for w in warnings {
+        eprintln!("warning: {}", w);
+    }
+    if args.brief {
+        if diff_root.has_changes() {
+            println!("Files {} and {} differ: {}", args.left, args.right, diff_root.summary());
+        }
+        return Ok(differ);
     }
 
-    match args.view {
-        crate::cli::View::Unified => render::render_tree(&diff_root)?,
-        crate::cli::View::SideBySide => render::render_side_by_side(&diff_root)?,
+    // keep machine-readable output parseable
+    for note in notes {
+        match args.format {
+            Format::Text => println!("note: {}", note),
+            Format::Sarif | Format::Junit | Format::Paths => eprintln!("note: {}", note),
+        }
     }
 
-    Ok(())
+    let color = args.color.enabled();
+    let (left, right) = (args.left.clone(), args.right.clone());
+    let renderer: Box<dyn render::Renderer> = match (args.format, args.view) {
+        (Format::Text, View::Unified) => Box::new(render::TreeRenderer { color, context: args.context() }),
+        (Format::Text, View::SideBySide) => Box::new(render::SideBySideRenderer {
+            color,
+            context: args.context(),
+            width: render::terminal_width(),
+            wrap: args.wrap,
+        }),
+        (Format::Sarif, _) => Box::new(report::SarifRenderer { left, right }),
+        (Format::Junit, _) => Box::new(report::JunitRenderer { left, right, fail_on: args.fail_on }),
+        (Format::Paths, _) => Box::new(report::PathsRenderer),
+    };
+    let mut out = std::io::stdout().lock();
+    renderer.render(diff_root, &mut out)?;
+
+    if args.summary {
+        // keep machine-readable output parseable
+        match args.format {
+            Format::Text => {
+                writeln!(out)?;
+                comparison.stats().write_report(SUMMARY_TOP, &mut out)?;
+            }
+            Format::Sarif | Format::Junit | Format::Paths => comparison.stats().write_report(SUMMARY_TOP, &mut std::io::stderr())?,
+        }
+    }
+
+    Ok(differ)
+}
+
+/// Tags and packages listed by `--summary`.
+const SUMMARY_TOP: usize = 5;
+
+/// Print statistics of the comparison described by `args`. Returns whether the inputs
+/// differ.
+pub fn stats(args: StatsArgs) -> Result<bool> {
+    let comparison = compare(&args.left, &args.right, &args.compare)?;
+    for w in &comparison.warnings {
+        eprintln!("warning: {}", w);
+    }
+    comparison.stats().write_report(args.top, &mut std::io::stdout().lock())?;
+    Ok(comparison.root.has_changes())
 }
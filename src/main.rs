@@ -1,7 +1,20 @@
-use anyhow::Result;
-use arxml_diff::cli::Args;
+use arxml_diff::cli::{Cli, Command};
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
-    let args = Args::parse_args();
-    arxml_diff::run(args)
+/// Exit status like diff(1): 0 without differences, 1 with differences, 2 on trouble.
+fn main() -> ExitCode {
+    let cli = Cli::parse_args();
+    let result = match (cli.command, cli.args) {
+        (Some(Command::Stats(args)), _) => arxml_diff::stats(args),
+        (None, Some(args)) => arxml_diff::run(args),
+        (None, None) => unreachable!("the inputs are required without a subcommand"),
+    };
+    match result {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("arxml-diff: {:#}", e);
+            ExitCode::from(2)
+        }
+    }
 }
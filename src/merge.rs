@@ -0,0 +1,156 @@
+use crate::diff::{is_mismatch, paired_children, DiffNode, DiffOptions, NodeStatus};
+use crate::parse::{Content, ContentNode, Document, Element};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Which side of a change goes into the merged document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Take {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Take {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Take::Left => "left",
+            Take::Right => "right",
+        })
+    }
+}
+
+/// Why the sides taken cannot be merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// The roots do not match and the removed and the added root were taken from
+    /// different sides, which would keep both or neither
+    RootConflict,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::RootConflict => f.write_str("the root elements differ: take the same side for the removed and the added root"),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Default output of a merge: the right input with `.merged.arxml` in place of its
+/// `.arxml` (and compression) suffix, or next to the archive it is in.
+pub fn default_merge_output(right: &str) -> PathBuf {
+    let mut file = right.split("!/").next().unwrap_or(right);
+    for suffix in [".gz", ".zst", ".zip", ".arxml"] {
+        file = file.strip_suffix(suffix).unwrap_or(file);
+    }
+    PathBuf::from(format!("{}.merged.arxml", file))
+}
+
+/// Merge two documents along their diff, made with `opts`. `take` picks the side of
+/// each change, given the child indices from the root of the diff to its node (see
+/// [`DiffNode::change_kind`]): the removed or added element is kept or left out, and a
+/// changed element keeps the values of that side. Everything else is the same on both
+/// sides and comes from the right, as do the XML declaration, prolog and epilog.
+/// Fails if the roots differ and the sides taken would keep both or neither.
+pub fn merge(left: &Document, right: &Document, diff: &DiffNode, opts: &DiffOptions, take: impl Fn(&[usize]) -> Take) -> Result<Document, MergeError> {
+    let root = if is_mismatch(opts, &left.root, &right.root) {
+        // a document has one root: keeping the removed one leaves out the added one
+        match (take(&[0]), take(&[1])) {
+            (Take::Left, Take::Left) => left.root.clone(),
+            (Take::Right, Take::Right) => right.root.clone(),
+            _ => return Err(MergeError::RootConflict),
+        }
+    } else {
+        merge_element(&left.root, &right.root, diff, opts, &take, &mut Vec::new())
+    };
+    Ok(Document {
+        declaration: right.declaration.clone(),
+        prolog: right.prolog.clone(),
+        root,
+        epilog: right.epilog.clone(),
+        warnings: Vec::new(),
+        encoding: right.encoding.clone(),
+    })
+}
+
+/// Merge the matched elements `l` and `r`, whose diff node `d` is at `indices`.
+fn merge_element(l: &Element, r: &Element, d: &DiffNode, opts: &DiffOptions, take: &dyn Fn(&[usize]) -> Take, indices: &mut Vec<usize>) -> Element {
+    if d.status == NodeStatus::Unchanged {
+        return r.clone();
+    }
+    // the element's own name, attributes and text come from the side taken for its
+    // values, and so do the ignored children and the content between children
+    let from_left = !d.changes.is_empty() && take(indices) == Take::Left;
+    let base = if from_left { l } else { r };
+    let index_in_base = |e: &Element| base.children.iter().position(|c| std::ptr::eq(c, e));
+
+    // merged children, with the index of the base child each one stands in for
+    let mut children: Vec<(Element, Option<usize>)> = Vec::new();
+    let (l_children, r_children, pairs) = paired_children(opts, l, r);
+    for (i, ((li, ri), child)) in pairs.into_iter().zip(&d.children).enumerate() {
+        let (le, re) = (li.map(|i| l_children[i]), ri.map(|i| r_children[i]));
+        let at = if from_left { le } else { re }.and_then(index_in_base);
+        indices.push(i);
+        match (le, re) {
+            (Some(le), Some(re)) if is_mismatch(opts, le, re) => {
+                // the removed element is the first child of the diff node, the added one the second
+                indices.push(0);
+                if take(indices) == Take::Left { children.push((le.clone(), at)); }
+                *indices.last_mut().unwrap() = 1;
+                if take(indices) == Take::Right { children.push((re.clone(), at)); }
+                indices.pop();
+            }
+            (Some(le), Some(re)) => children.push((merge_element(le, re, child, opts, take, indices), at)),
+            (Some(le), None) => if take(indices) == Take::Left { children.push((le.clone(), at)) },
+            (None, Some(re)) => if take(indices) == Take::Right { children.push((re.clone(), at)) },
+            (None, None) => {}
+        }
+        indices.pop();
+    }
+    for (k, c) in base.children.iter().enumerate().filter(|(_, c)| opts.is_ignored(c)) {
+        let pos = children.iter().position(|(_, at)| at.is_some_and(|a| a > k)).unwrap_or(children.len());
+        children.insert(pos, (c.clone(), Some(k)));
+    }
+
+    let content = merged_content(base, &children);
+    Element {
+        tag: base.tag.clone(),
+        namespace: base.namespace.clone(),
+        prefix: base.prefix.clone(),
+        short_name: base.short_name.clone(),
+        short_name_element: base.short_name_element.clone(),
+        uuid: base.uuid.clone(),
+        attributes: base.attributes.clone(),
+        content,
+        children: children.into_iter().map(|(e, _)| e).collect(),
+        position: None,
+    }
+}
+
+/// Content of `base` around the merged `children`: each item goes before the child that
+/// stands for the base child it preceded. The indentation of a base child that is left
+/// out goes with it, and children from the other side are indented like their siblings.
+fn merged_content(base: &Element, children: &[(Element, Option<usize>)]) -> Vec<Content> {
+    let is_blank = |c: &Content| matches!(&c.node, ContentNode::Text(t) if t.trim().is_empty());
+    // blank text right before base child `k`
+    let indent_of = |k: usize| base.content.iter().rfind(|c| c.pos == k).filter(|c| is_blank(c));
+    let mut kept = vec![false; base.children.len()];
+    for at in children.iter().filter_map(|(_, at)| *at) {
+        kept[at] = true;
+    }
+    let mut content: Vec<Content> = base.content.iter()
+        .filter(|c| kept.get(c.pos).is_none_or(|k| *k) || !indent_of(c.pos).is_some_and(|i| std::ptr::eq(i, *c)))
+        .map(|c| Content {
+            pos: children.iter().position(|(_, at)| at.is_some_and(|a| a >= c.pos)).unwrap_or(children.len()),
+            node: c.node.clone(),
+        })
+        .collect();
+    if let Some(indent) = (0..base.children.len()).find_map(indent_of) {
+        for (j, _) in children.iter().enumerate().filter(|(_, (_, at))| at.is_none()) {
+            content.push(Content { pos: j, node: indent.node.clone() });
+        }
+        content.sort_by_key(|c| c.pos);
+    }
+    content
+}
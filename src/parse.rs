@@ -1,136 +1,748 @@
-use quick_xml::events::Event;
-use quick_xml::Reader;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use anyhow::Result;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use quick_xml::encoding::{detect_encoding, Decoder};
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::NsReader;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::BufRead;
+use crate::error::{Error, Result};
 
+/// A parsed ARXML document: the optional XML declaration plus the root element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    /// Raw content of the `<?xml ...?>` declaration, e.g. `xml version="1.0" encoding="UTF-8"`
+    pub declaration: Option<String>,
+    /// Comments and processing instructions between the declaration and the root element
+    pub prolog: Vec<ContentNode>,
+    pub root: Element,
+    /// Comments and processing instructions after the root element
+    pub epilog: Vec<ContentNode>,
+    /// Problems that were recovered from in lenient mode
+    pub warnings: Vec<Diagnostic>,
+    /// Name of the source encoding (from the BOM or the declaration), e.g. `UTF-8`,
+    /// `UTF-16LE` or `windows-1252` for a declared ISO-8859-1
+    pub encoding: String,
+}
+
+impl Document {
+    /// Serialize the declaration (if any), the prolog, the root element and the epilog.
+    pub fn serialize(&self) -> String {
+        let mut s = String::new();
+        if let Some(d) = &self.declaration {
+            s.push_str("<?");
+            s.push_str(d);
+            s.push_str("?>\n");
+        }
+        for node in &self.prolog {
+            write_content(&mut s, node);
+            s.push('\n');
+        }
+        s.push_str(&self.root.serialize());
+        for node in &self.epilog {
+            s.push('\n');
+            write_content(&mut s, node);
+        }
+        s
+    }
+
+    /// Serialize into bytes in the source encoding, so the declaration stays truthful.
+    /// UTF-16 output starts with a byte order mark.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let s = self.serialize();
+        let encoding = Encoding::for_label(self.encoding.as_bytes()).unwrap_or(UTF_8);
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            let mut out = Vec::with_capacity(2 + s.len() * 2);
+            for unit in std::iter::once(0xFEFF).chain(s.encode_utf16()) {
+                let bytes = if encoding == UTF_16LE { unit.to_le_bytes() } else { unit.to_be_bytes() };
+                out.extend_from_slice(&bytes);
+            }
+            return out;
+        }
+        encoding.encode(&s).0.into_owned()
+    }
+}
+
+/// Non-element content of an element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentNode {
+    Text(String),
+    CData(String),
+    Comment(String),
+    ProcessingInstruction(String),
+    /// Where the SHORT-NAME element (lifted into [`Element::short_name`]) appeared
+    ShortName,
+}
+
+/// A piece of non-element content, anchored before the child at index `pos`
+/// (or after the last child if `pos >= children.len()`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content {
+    pub pos: usize,
+    pub node: ContentNode,
+}
+
+/// Namespace URI of `xsi:*` attributes such as `xsi:schemaLocation`.
+pub const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Line and column (1-based, in characters) of an element's start tag in its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Equality means the same source, apart from the [`Position`] in it: prefixes,
+/// attribute order and whitespace between children count. The diff does not use it;
+/// it compares names by namespace (see [`Element::same_name`]) and ignores layout.
 #[derive(Debug, Clone)]
 pub struct Element {
+    /// Local name of the element (without namespace prefix)
     pub tag: String,
+    /// Resolved namespace URI of the element, if it is bound to one
+    pub namespace: Option<String>,
+    /// Namespace prefix as written in the source, kept for serialization only
+    pub prefix: Option<String>,
     pub short_name: Option<String>,
+    /// The SHORT-NAME element as it was parsed, so it is written back with its own
+    /// prefix and attributes
+    pub short_name_element: Option<Box<Element>>,
     pub uuid: Option<String>,
-    pub attributes: HashMap<String, String>,
-    pub text: Option<String>,
+    /// Attributes in source order (including `xmlns` declarations)
+    pub attributes: Vec<(String, String)>,
+    /// Text (whitespace between children included), comments, CDATA sections and
+    /// processing instructions in source order, interleaved with `children` by position
+    pub content: Vec<Content>,
     pub children: Vec<Element>,
+    /// Where the element starts in its source, if it was parsed
+    pub position: Option<Position>,
+}
+
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+            && self.namespace == other.namespace
+            && self.prefix == other.prefix
+            && self.short_name == other.short_name
+            && self.short_name_element == other.short_name_element
+            && self.uuid == other.uuid
+            && self.attributes == other.attributes
+            && self.content == other.content
+            && self.children == other.children
+    }
 }
 
 impl Element {
     pub fn new(tag: String) -> Self {
-        Self { tag, short_name: None, uuid: None, attributes: HashMap::new(), text: None, children: vec![] }
+        Self { tag, namespace: None, prefix: None, short_name: None, short_name_element: None, uuid: None, attributes: Vec::new(), content: Vec::new(), children: vec![], position: None }
     }
 
-    /// Serialize this element and its subtree into a compact XML string.
-    pub fn serialize(&self) -> String {
-        fn escape(s: &str) -> String {
-            s.replace('&', "&amp;")
-             .replace('<', "&lt;")
-             .replace('>', "&gt;")
-             .replace('"', "&quot;")
+    /// The element name as written in the source, including its prefix.
+    pub fn qualified_name(&self) -> Cow<'_, str> {
+        match &self.prefix {
+            Some(p) => Cow::Owned(format!("{}:{}", p, self.tag)),
+            None => Cow::Borrowed(&self.tag),
+        }
+    }
+
+    /// Whether both elements have the same local name and namespace URI,
+    /// regardless of the prefixes used to write them.
+    pub fn same_name(&self, other: &Element) -> bool {
+        self.tag == other.tag && self.namespace == other.namespace
+    }
+
+    /// Text content of this element (text and CDATA), trimmed. Text around child
+    /// elements is joined with a single space. `None` if there is no text.
+    pub fn text(&self) -> Option<String> {
+        let mut out = String::new();
+        let mut run = String::new();
+        let mut run_pos = None;
+        for c in &self.content {
+            let t = match &c.node {
+                ContentNode::Text(t) | ContentNode::CData(t) => t,
+                _ => continue,
+            };
+            if run_pos != Some(c.pos) {
+                push_trimmed(&mut out, &run);
+                run.clear();
+                run_pos = Some(c.pos);
+            }
+            run.push_str(t);
         }
+        push_trimmed(&mut out, &run);
+        if out.is_empty() { None } else { Some(out) }
+    }
+
+    /// Number of elements in this subtree as written in the source, SHORT-NAMEs included.
+    pub fn element_count(&self) -> usize {
+        1 + usize::from(self.short_name.is_some()) + self.children.iter().map(Element::element_count).sum::<usize>()
+    }
+
+    fn has_short_name_marker(&self) -> bool {
+        self.content.iter().any(|c| c.node == ContentNode::ShortName)
+    }
+
+    /// Look up an attribute value by its (qualified) name.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Namespace declarations (`xmlns` / `xmlns:*` attributes) made on this element.
+    pub fn namespace_declarations(&self) -> impl Iterator<Item = &(String, String)> {
+        self.attributes.iter().filter(|(k, _)| is_namespace_declaration(k))
+    }
+
+    /// Namespace declarations in scope for the children of this element, given the
+    /// declarations in scope for the element itself.
+    pub fn child_scope<'a>(&self, scope: &'a [(String, String)]) -> Cow<'a, [(String, String)]> {
+        if self.namespace_declarations().next().is_none() {
+            return Cow::Borrowed(scope);
+        }
+        let mut out = scope.to_vec();
+        for (k, v) in self.namespace_declarations() {
+            match out.iter_mut().find(|(ok, _)| ok == k) {
+                Some(existing) => existing.1 = v.clone(),
+                None => out.push((k.clone(), v.clone())),
+            }
+        }
+        Cow::Owned(out)
+    }
 
+    /// Attributes keyed by resolved `(namespace URI, local name)`, excluding namespace
+    /// declarations. `scope` must include this element's own declarations
+    /// (see [`Element::child_scope`]). Prefixes that cannot be resolved are kept verbatim.
+    pub fn resolved_attributes<'a>(&'a self, scope: &'a [(String, String)]) -> Vec<(Option<&'a str>, &'a str, &'a str)> {
+        self.attributes.iter()
+            .filter(|(k, _)| !is_namespace_declaration(k))
+            .map(|(k, v)| match k.split_once(':') {
+                Some((prefix, local)) => {
+                    let decl = format!("xmlns:{}", prefix);
+                    match scope.iter().rev().find(|(d, _)| *d == decl) {
+                        Some((_, uri)) => (Some(uri.as_str()), local, v.as_str()),
+                        None => (None, k.as_str(), v.as_str()),
+                    }
+                }
+                None => (None, k.as_str(), v.as_str()),
+            })
+            .collect()
+    }
+
+    /// Value of `xsi:schemaLocation`, whatever prefix is bound to the XSI namespace.
+    pub fn schema_location<'a>(&'a self, scope: &'a [(String, String)]) -> Option<&'a str> {
+        self.resolved_attributes(scope).into_iter()
+            .find(|(ns, local, _)| *ns == Some(XSI_NAMESPACE) && *local == "schemaLocation")
+            .map(|(_, _, v)| v)
+    }
+
+    /// Serialize this element and its subtree into an XML string, laid out as in the source.
+    pub fn serialize(&self) -> String {
+        self.serialize_in_scope(&[])
+    }
+
+    /// Serialize this element as a standalone fragment: namespace declarations inherited
+    /// from ancestors (`scope`) are re-emitted on the fragment root unless it redeclares them.
+    pub fn serialize_in_scope(&self, scope: &[(String, String)]) -> String {
         let mut s = String::new();
+        self.write_xml(&mut s, scope);
+        s
+    }
+
+    fn write_xml(&self, s: &mut String, scope: &[(String, String)]) {
+        let name = self.qualified_name();
         s.push('<');
-        s.push_str(&self.tag);
+        s.push_str(&name);
         for (k, v) in &self.attributes {
-            s.push(' ');
-            s.push_str(k);
-            s.push_str("=\"");
-            s.push_str(&escape(v));
-            s.push('"');
+            push_attribute(s, k, v);
+        }
+        for (k, v) in scope {
+            if self.attribute(k).is_none() {
+                push_attribute(s, k, v);
+            }
         }
 
-        if self.children.is_empty() && self.text.is_none() {
+        if self.children.is_empty() && self.content.is_empty() {
             s.push_str("/>");
-            return s;
+            return;
         }
 
         s.push('>');
-        if let Some(t) = &self.text {
-            s.push_str(&escape(t));
+        let mut content = self.content.iter().peekable();
+        for (i, c) in self.children.iter().enumerate() {
+            while let Some(item) = content.next_if(|item| item.pos <= i) {
+                self.write_content_node(s, &item.node);
+            }
+            c.write_xml(s, &[]);
         }
-        for c in &self.children {
-            s.push_str(&c.serialize());
+        for item in content {
+            self.write_content_node(s, &item.node);
         }
         s.push_str("</");
-        s.push_str(&self.tag);
+        s.push_str(&name);
         s.push('>');
-        s
+    }
+
+    fn write_content_node(&self, s: &mut String, node: &ContentNode) {
+        match node {
+            ContentNode::ShortName => self.write_short_name(s),
+            other => write_content(s, other),
+        }
+    }
+
+    fn write_short_name(&self, s: &mut String) {
+        if let Some(el) = &self.short_name_element {
+            if el.text() == self.short_name {
+                el.write_xml(s, &[]);
+            } else {
+                // The name was changed after parsing: keep the tag and attributes
+                // of the original element and replace its text
+                let mut el = Element { content: Vec::new(), children: Vec::new(), ..(**el).clone() };
+                if let Some(sn) = &self.short_name {
+                    el.content.push(Content { pos: 0, node: ContentNode::Text(sn.clone()) });
+                }
+                el.write_xml(s, &[]);
+            }
+            return;
+        }
+        // Without a parsed element, SHORT-NAME shares the namespace prefix of its parent
+        let mut el = Element::new("SHORT-NAME".to_string());
+        el.prefix = self.prefix.clone();
+        if let Some(sn) = &self.short_name {
+            el.content.push(Content { pos: 0, node: ContentNode::Text(sn.clone()) });
+        }
+        el.write_xml(s, &[]);
+    }
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+     .replace('"', "&quot;")
+}
+
+fn write_content(s: &mut String, node: &ContentNode) {
+    match node {
+        ContentNode::Text(t) => s.push_str(&escape(t)),
+        ContentNode::CData(t) => {
+            s.push_str("<![CDATA[");
+            s.push_str(t);
+            s.push_str("]]>");
+        }
+        ContentNode::Comment(t) => {
+            s.push_str("<!--");
+            s.push_str(t);
+            s.push_str("-->");
+        }
+        ContentNode::ProcessingInstruction(t) => {
+            s.push_str("<?");
+            s.push_str(t);
+            s.push_str("?>");
+        }
+        ContentNode::ShortName => {}
+    }
+}
+
+fn push_trimmed(out: &mut String, t: &str) {
+    let t = t.trim();
+    if t.is_empty() { return; }
+    if !out.is_empty() { out.push(' '); }
+    out.push_str(t);
+}
+
+fn push_attribute(s: &mut String, k: &str, v: &str) {
+    s.push(' ');
+    s.push_str(k);
+    s.push_str("=\"");
+    s.push_str(&escape(v));
+    s.push('"');
+}
+
+fn is_namespace_declaration(key: &str) -> bool {
+    key == "xmlns" || key.starts_with("xmlns:")
+}
+
+/// Options controlling how input is parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Recover from mismatched end tags, bad entities and truncated input instead of
+    /// failing. What was recovered from is reported in [`Document::warnings`].
+    pub lenient: bool,
+}
+
+/// A parse problem with its location in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column (in characters)
+    pub column: usize,
+    pub message: String,
+    /// The offending source line
+    pub snippet: String,
+    /// Qualified names of the elements open at that point, outermost first
+    pub open_elements: Vec<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.path, self.line, self.column, self.message)?;
+        write!(f, "\n{:>6} | {}", self.line, self.snippet)?;
+        if !self.open_elements.is_empty() {
+            write!(f, "\n{:>6} | in {}", "", self.open_elements.join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// The reader only handles ASCII-compatible encodings, so UTF-16 input is transcoded
+/// to UTF-8 up front. Returns the text and the UTF-16 variant it was decoded from.
+fn transcode_utf16(src: &[u8]) -> Option<(String, &'static Encoding)> {
+    match detect_encoding(src) {
+        Some((enc, bom_len)) if enc == UTF_16LE || enc == UTF_16BE => {
+            let (text, _) = enc.decode_without_bom_handling(&src[bom_len..]);
+            Some((text.into_owned(), enc))
+        }
+        _ => None,
+    }
+}
+
+/// Decode names and raw content in the document encoding.
+fn decode(decoder: Decoder, bytes: &[u8]) -> String {
+    match decoder.decode(bytes) {
+        Ok(s) => s.into_owned(),
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
     }
 }
 
-fn local_name(name: &str) -> &str {
-    match name.rfind(':') {
-        Some(idx) => &name[idx+1..],
-        None => name,
+/// Offset in escaped text of the first entity or character reference that cannot be
+/// unescaped.
+fn bad_entity(raw: &[u8]) -> Option<usize> {
+    raw.iter().enumerate().filter(|(_, b)| **b == b'&').map(|(i, _)| i).find(|&i| {
+        let end = raw[i..].iter().position(|b| *b == b';').map_or(raw.len(), |p| i + p + 1);
+        std::str::from_utf8(&raw[i..end]).map_or(true, |r| unescape(r).is_err())
+    })
+}
+
+/// Longest snippet of the offending line shown in a diagnostic
+const SNIPPET_MAX_CHARS: usize = 120;
+
+/// Builds the element tree from reader events and keeps track of where problems occur.
+struct TreeBuilder<'a> {
+    path: &'a str,
+    src: &'a [u8],
+    lenient: bool,
+    stack: Vec<Element>,
+    declaration: Option<String>,
+    prolog: Vec<ContentNode>,
+    /// The root element, once it is closed
+    root: Option<Element>,
+    epilog: Vec<ContentNode>,
+    warnings: Vec<Diagnostic>,
+    /// (offset, line number at offset, start of that line, column at offset): speeds
+    /// up locating elements and diagnostics, which arrive with increasing offsets
+    line_cache: (usize, usize, usize, usize),
+    /// Encoding of `src`, known once the XML declaration is read
+    encoding: &'static Encoding,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn new(path: &'a str, src: &'a [u8], opts: &ParseOptions) -> Self {
+        Self {
+            path, src, lenient: opts.lenient, stack: Vec::new(), declaration: None,
+            prolog: Vec::new(), root: None, epilog: Vec::new(), warnings: Vec::new(), line_cache: (0, 1, 0, 1),
+            encoding: UTF_8,
+        }
+    }
+
+    /// Number of characters in `bytes` of the source.
+    fn char_count(&self, bytes: &[u8]) -> usize {
+        if self.encoding == UTF_8 {
+            // count characters, not UTF-8 continuation bytes
+            bytes.iter().filter(|b| **b & 0xc0 != 0x80).count()
+        } else if self.encoding.is_single_byte() {
+            bytes.len()
+        } else {
+            self.encoding.decode_without_bom_handling(bytes).0.chars().count()
+        }
+    }
+
+    /// Position of `offset` in the source. Returns the start of its line as well.
+    fn locate(&mut self, offset: usize) -> (Position, usize) {
+        let offset = offset.min(self.src.len());
+        let (mut from, mut line, mut line_start, mut column) = self.line_cache;
+        if offset < from {
+            (from, line, line_start, column) = (0, 1, 0, 1);
+        }
+        if from == 0 && self.src.starts_with(UTF8_BOM) {
+            (from, line_start) = (UTF8_BOM.len(), UTF8_BOM.len());
+        }
+        let offset = offset.max(from);
+        // a line feed is never part of a multi-byte character in the encodings read here
+        let span = &self.src[from..offset];
+        let last_line = match span.iter().rposition(|b| *b == b'\n') {
+            Some(i) => {
+                line += span[..i].iter().filter(|b| **b == b'\n').count() + 1;
+                line_start = from + i + 1;
+                column = 1;
+                &span[i + 1..]
+            }
+            None => span,
+        };
+        column += self.char_count(last_line);
+        self.line_cache = (offset, line, line_start, column);
+        (Position { line, column }, line_start)
+    }
+
+    fn diagnostic(&mut self, offset: usize, message: String) -> Diagnostic {
+        let (Position { line, column }, line_start) = self.locate(offset);
+        let line_end = self.src[line_start..].iter().position(|b| *b == b'\n').map_or(self.src.len(), |p| line_start + p);
+        let (text, _) = self.encoding.decode_without_bom_handling(&self.src[line_start..line_end]);
+        let mut snippet: String = text.trim_end().chars().take(SNIPPET_MAX_CHARS).collect();
+        if text.trim_end().chars().count() > SNIPPET_MAX_CHARS { snippet.push('…'); }
+
+        Diagnostic {
+            path: self.path.to_string(),
+            line,
+            column,
+            message,
+            snippet,
+            open_elements: self.stack.iter().map(|e| e.qualified_name().into_owned()).collect(),
+        }
+    }
+
+    /// Report a problem that lenient mode can recover from: an error in strict
+    /// mode, a warning otherwise.
+    fn recover(&mut self, offset: usize, message: String) -> std::result::Result<(), Diagnostic> {
+        let d = self.diagnostic(offset, message);
+        if self.lenient {
+            self.warnings.push(d);
+            Ok(())
+        } else {
+            Err(d)
+        }
+    }
+
+    fn start_element<R>(&mut self, reader: &NsReader<R>, e: &BytesStart, offset: usize) -> std::result::Result<Element, Diagnostic> {
+        let decoder = reader.decoder();
+        let (ns, local) = reader.resolve_element(e.name());
+        let mut el = Element::new(decode(decoder, local.as_ref()));
+        el.position = Some(self.locate(offset).0);
+        if let ResolveResult::Bound(ns) = ns {
+            el.namespace = Some(decode(decoder, ns.as_ref()));
+        }
+        el.prefix = e.name().prefix().map(|p| decode(decoder, p.as_ref()));
+        for a in e.attributes().with_checks(false).flatten() {
+            let key = decode(decoder, a.key.as_ref());
+            let v = match a.decode_and_unescape_value(reader) {
+                Ok(val) => val.to_string(),
+                Err(err) => {
+                    self.recover(offset, format!("attribute {}: {}", key, err))?;
+                    decode(decoder, &a.value)
+                }
+            };
+            if key.eq_ignore_ascii_case("UUID") { el.uuid = Some(v.clone()); }
+            el.attributes.push((key, v));
+        }
+        Ok(el)
+    }
+
+    /// Non-element content goes to the open element, or to the prolog before the root
+    /// and the epilog after it
+    fn push_content(&mut self, node: ContentNode) {
+        match self.stack.last_mut() {
+            Some(last) => last.content.push(Content { pos: last.children.len(), node }),
+            None if matches!(node, ContentNode::Text(_) | ContentNode::CData(_)) => {}
+            None if self.root.is_some() => self.epilog.push(node),
+            None => self.prolog.push(node),
+        }
+    }
+
+    /// Close the innermost open element. Returns the root once it is closed.
+    fn close(&mut self) -> Option<Element> {
+        let el = self.stack.pop()?;
+        finish_element(&mut self.stack, el)
+    }
+
+    fn into_document(self, root: Element, encoding: &'static Encoding) -> Document {
+        Document {
+            declaration: self.declaration,
+            prolog: self.prolog,
+            root,
+            epilog: self.epilog,
+            warnings: self.warnings,
+            encoding: encoding.name().to_string(),
+        }
     }
 }
 
+/// Attach a finished element to its parent. Returns the element back if it was the root.
+fn finish_element(stack: &mut [Element], el: Element) -> Option<Element> {
+    let Some(parent) = stack.last_mut() else { return Some(el) };
+    // If this element is a SHORT-NAME, attach its text to the parent.short_name
+    // and remember where it was, but do not add it as a child to avoid duplication
+    if el.tag.eq_ignore_ascii_case("SHORT-NAME") && !parent.has_short_name_marker() {
+        parent.short_name = el.text();
+        parent.short_name_element = Some(Box::new(el));
+        parent.content.push(Content { pos: parent.children.len(), node: ContentNode::ShortName });
+    } else {
+        parent.children.push(el);
+    }
+    None
+}
+
 pub fn parse_file(path: &str) -> Result<Element> {
-    let file = File::open(path)?;
-    let mut reader = Reader::from_reader(BufReader::new(file));
-    reader.trim_text(true);
+    Ok(parse_document(path)?.root)
+}
+
+pub fn parse_document(path: &str) -> Result<Document> {
+    parse_document_with(path, &ParseOptions::default())
+}
+
+/// Parse a document held in a string. The text is taken as it is, whatever encoding
+/// its XML declaration names.
+pub fn parse_str(xml: &str) -> Result<Document> {
+    parse_str_with(xml, &ParseOptions::default())
+}
+
+pub fn parse_str_with(xml: &str, opts: &ParseOptions) -> Result<Document> {
+    Ok(parse_events(xml.as_bytes(), NsReader::from_str(xml), None, "<string>", opts)?)
+}
 
+/// Parse a document from a reader. Like files, the data may be in any supported
+/// encoding and gzip/zstd compressed.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<Document> {
+    parse_reader_with(reader, &ParseOptions::default())
+}
+
+pub fn parse_reader_with<R: BufRead>(mut reader: R, opts: &ParseOptions) -> Result<Document> {
+    const ORIGIN: &str = "<reader>";
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| Error::io(ORIGIN, e))?;
+    let data = crate::input::decompress(data, ORIGIN)?;
+    Ok(parse_bytes(&data, ORIGIN, opts)?)
+}
+
+/// Parse a document from `path`, which may also be gzip/zstd compressed or name an
+/// entry inside a zip archive (`bundle.zip!/path/System.arxml`), see [`crate::input`].
+pub fn parse_document_with(path: &str, opts: &ParseOptions) -> Result<Document> {
+    let src = crate::input::read_input(path)?;
+    Ok(parse_bytes(&src, path, opts)?)
+}
+
+/// Parse an in-memory document in any encoding. `path` is only used in diagnostics.
+pub(crate) fn parse_bytes(src: &[u8], path: &str, opts: &ParseOptions) -> std::result::Result<Document, Diagnostic> {
+    match transcode_utf16(src) {
+        // a reader created from a str keeps decoding UTF-8, whatever the declaration says
+        Some((text, enc)) => parse_events(text.as_bytes(), NsReader::from_str(&text), Some(enc), path, opts),
+        None => parse_events(src, NsReader::from_reader(src), None, path, opts),
+    }
+}
+
+/// Build the document from the events of `reader`, which reads `src`. The source
+/// encoding is the one the reader decodes, unless `transcoded_from` is given.
+fn parse_events(src: &[u8], mut reader: NsReader<&[u8]>, transcoded_from: Option<&'static Encoding>, path: &str, opts: &ParseOptions) -> std::result::Result<Document, Diagnostic> {
+    // in lenient mode mismatched end tags are matched up by the builder
+    reader.check_end_names(!opts.lenient);
+
+    let mut b = TreeBuilder::new(path, src, opts);
     let mut buf = Vec::new();
-    let mut stack: Vec<Element> = Vec::new();
 
     loop {
+        buf.clear();
+        let offset = reader.buffer_position();
         match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
-                let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                let mut el = Element::new(tag);
-                for attr in e.attributes().with_checks(false) {
-                    if let Ok(a) = attr {
-                        let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
-                        if let Ok(val) = a.unescape_value() {
-                            let v = val.to_string();
-                            el.attributes.insert(key.clone(), v.clone());
-                            if key.eq_ignore_ascii_case("UUID") { el.uuid = Some(v); }
-                        }
-                    }
+            // after the root only comments and processing instructions may follow
+            Ok(Event::Start(e) | Event::Empty(e)) if b.root.is_some() => {
+                let name = decode(reader.decoder(), e.name().as_ref());
+                b.recover(offset, format!("element <{}> after the root element ignored", name))?;
+                // there is nowhere to put it: keep what was read so far
+                break;
+            }
+            Ok(Event::Decl(d)) => {
+                b.declaration = Some(decode(reader.decoder(), &d));
+                if transcoded_from.is_none() {
+                    b.encoding = reader.decoder().encoding();
                 }
-                stack.push(el);
+            }
+            Ok(Event::Start(e)) => {
+                let el = b.start_element(&reader, &e, offset)?;
+                b.stack.push(el);
+            }
+            Ok(Event::Empty(e)) => {
+                let el = b.start_element(&reader, &e, offset)?;
+                b.root = finish_element(&mut b.stack, el);
             }
             Ok(Event::Text(e)) => {
-                if let Some(last) = stack.last_mut() {
-                    let txt = e.unescape().unwrap_or_else(|_| std::borrow::Cow::Borrowed(""));
-                    let t = txt.trim().to_string();
-                    if !t.is_empty() {
-                        // Accumulate text for the current element (may arrive in several chunks)
-                        match &mut last.text {
-                            Some(existing) => {
-                                existing.push(' ');
-                                existing.push_str(&t);
-                            }
-                            None => last.text = Some(t),
-                        }
+                let txt = match e.unescape() {
+                    Ok(t) => t.to_string(),
+                    Err(err) => {
+                        b.recover(offset + bad_entity(&e).unwrap_or(0), err.to_string())?;
+                        decode(reader.decoder(), &e)
                     }
-                }
+                };
+                b.push_content(ContentNode::Text(txt));
+            }
+            Ok(Event::CData(e)) => {
+                let txt = decode(reader.decoder(), &e);
+                b.push_content(ContentNode::CData(txt));
+            }
+            Ok(Event::Comment(e)) => {
+                let txt = decode(reader.decoder(), &e);
+                b.push_content(ContentNode::Comment(txt));
             }
-            Ok(Event::End(_e)) => {
-                if let Some(el) = stack.pop() {
-                    // If this element is a SHORT-NAME, attach its text to the parent.short_name
-                    if local_name(&el.tag).eq_ignore_ascii_case("SHORT-NAME") {
-                        if let Some(parent) = stack.last_mut() {
-                            parent.short_name = el.text.clone();
-                            // do not add the SHORT-NAME as a child to avoid duplication
+            Ok(Event::PI(e)) => {
+                let txt = decode(reader.decoder(), &e);
+                b.push_content(ContentNode::ProcessingInstruction(txt));
+            }
+            Ok(Event::End(e)) => {
+                if opts.lenient {
+                    let name = decode(reader.decoder(), e.name().as_ref());
+                    match b.stack.iter().rposition(|el| el.qualified_name() == name) {
+                        None => {
+                            b.recover(offset, format!("unexpected end tag </{}> ignored", name))?;
                             continue;
                         }
-                    }
-
-                    if let Some(parent) = stack.last_mut() {
-                        parent.children.push(el);
-                    } else {
-                        // root element finished
-                        return Ok(el);
+                        Some(i) => {
+                            while b.stack.len() > i + 1 {
+                                let open = b.stack[b.stack.len() - 1].qualified_name().into_owned();
+                                b.recover(offset, format!("<{}> implicitly closed by </{}>", open, name))?;
+                                b.close();
+                            }
+                        }
                     }
                 }
+                b.root = b.close();
             }
             Ok(Event::Eof) => break,
-            Err(e) => return Err(anyhow::anyhow!(e)),
+            Err(e) => {
+                b.recover(offset, e.to_string())?;
+                // the reader cannot resume after a syntax error: keep what was read so far
+                break;
+            }
             _ => {}
         }
-        buf.clear();
     }
-    Err(anyhow::anyhow!("No root element found"))
+
+    let encoding = transcoded_from.unwrap_or(reader.decoder().encoding());
+    if let Some(root) = b.root.take() {
+        return Ok(b.into_document(root, encoding));
+    }
+    // report end-of-input problems on the last non-blank line
+    let end = src.trim_ascii_end().len();
+    if b.stack.is_empty() {
+        return Err(b.diagnostic(end, "no root element found".to_string()));
+    }
+    let open = b.stack.len();
+    b.recover(end, format!("unexpected end of input with {} element(s) still open", open))?;
+    loop {
+        if let Some(root) = b.close() {
+            return Ok(b.into_document(root, encoding));
+        }
+    }
 }
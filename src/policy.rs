@@ -0,0 +1,136 @@
+use crate::diff::{ChangeKind, DiffNode, NodeStatus};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::fmt;
+
+/// Default policy for AUTOSAR models: removing or changing interfaces, ports and
+/// data types is an error, other removals and changes are warnings, and additions
+/// and documentation changes are informational.
+const AUTOSAR_POLICY: &str = include_str!("../policies/autosar.json");
+
+/// How much a change matters.
+#[derive(clap::ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Assigns `severity` to the changes it matches. All given conditions must hold.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Glob on the element tag, e.g. `*-INTERFACE`
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Glob on the tag of the element or any of its ancestors
+    #[serde(default)]
+    pub within: Option<String>,
+    /// Glob on the AUTOSAR path: `*` stays within one level, `**` spans levels
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Change kinds the rule applies to; all kinds when empty
+    #[serde(default)]
+    pub kind: Vec<ChangeKind>,
+    pub severity: Severity,
+}
+
+impl Rule {
+    fn matches(&self, tags: &[&str], path: &str, kind: ChangeKind) -> bool {
+        let tag = tags.last().copied().unwrap_or("");
+        self.tag.as_deref().is_none_or(|p| glob_match(p, tag))
+            && self.within.as_deref().is_none_or(|p| tags.iter().any(|t| glob_match(p, t)))
+            && self.path.as_deref().is_none_or(|p| glob_match(p, path))
+            && (self.kind.is_empty() || self.kind.contains(&kind))
+    }
+}
+
+/// An ordered list of rules; the first matching rule decides the severity.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Severity of changes no rule matches
+    #[serde(default = "default_severity")]
+    pub default: Severity,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+fn default_severity() -> Severity {
+    Severity::Info
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::autosar()
+    }
+}
+
+impl Policy {
+    /// The built-in AUTOSAR policy, see `policies/autosar.json`.
+    pub fn autosar() -> Self {
+        Policy::from_json(AUTOSAR_POLICY).expect("built-in policy is valid")
+    }
+
+    pub fn from_json(json: &str) -> std::result::Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Read a policy from a JSON file.
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        Policy::from_json(&json).map_err(|e| Error::input(path, format_args!("invalid policy: {}", e)))
+    }
+
+    /// Severity of a change to the element with the innermost tag of `tags`.
+    pub fn severity(&self, tags: &[&str], path: &str, kind: ChangeKind) -> Severity {
+        self.rules.iter()
+            .find(|r| r.matches(tags, path, kind))
+            .map_or(self.default, |r| r.severity)
+    }
+
+    /// Set the severity of every change in the tree, see [`DiffNode::change_kind`].
+    pub fn apply(&self, root: &mut DiffNode) {
+        self.apply_node(root, None, &mut Vec::new());
+    }
+
+    fn apply_node<'a>(&self, node: &'a mut DiffNode, parent: Option<NodeStatus>, tags: &mut Vec<&'a str>) {
+        let kind = node.change_kind(parent);
+        let status = node.status;
+        let DiffNode { tag, path, severity, children, .. } = node;
+        tags.push(tag.as_str());
+        *severity = kind.map(|k| self.severity(tags, path, k));
+        for c in children {
+            self.apply_node(c, Some(status), tags);
+        }
+        tags.pop();
+    }
+}
+
+/// Match `text` against a glob where `*` and `?` do not cross `/` and `**` does.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn rec(p: &[u8], t: &[u8]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=t.len()).any(|i| rec(rest, &t[i..])),
+            [b'*', rest @ ..] => {
+                let level = t.iter().position(|&c| c == b'/').unwrap_or(t.len());
+                (0..=level).any(|i| rec(rest, &t[i..]))
+            }
+            [b'?', rest @ ..] => matches!(t, [c, ..] if *c != b'/') && rec(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && rec(rest, &t[1..]),
+        }
+    }
+    rec(pattern.as_bytes(), text.as_bytes())
+}
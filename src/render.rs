@@ -1,110 +1,340 @@
 use crate::diff::{DiffNode, NodeStatus};
-use crossterm::style::Stylize;
-use anyhow::Result;
-
-fn render_node(node: &DiffNode, indent: usize) {
-    let prefix = match node.status {
-        NodeStatus::Added => "+".green().to_string(),
-        NodeStatus::Removed => "-".red().to_string(),
-        NodeStatus::Changed => "~".yellow().to_string(),
-        NodeStatus::Unchanged => " ".to_string(),
-    };
+use crate::policy::Severity;
+use crossterm::style::{Color, Stylize};
+use std::io::{self, IsTerminal, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-    let name = node.key.as_deref().unwrap_or(&node.tag);
-    let indent_str = "  ".repeat(indent);
-    println!("{}{} {}", indent_str, prefix, name);
+/// When to emit ANSI colour codes.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Colour when stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
 
-    for c in &node.children {
-        render_node(c, indent + 1);
+impl ColorChoice {
+    /// Whether output to stdout should be coloured.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stdout().is_terminal()
+            }
+        }
     }
 }
 
-pub fn render_tree(root: &DiffNode) -> Result<()> {
-    render_node(root, 0);
-    Ok(())
+/// Writes a diff tree in some output format.
+pub trait Renderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()>;
 }
 
-fn compute_max_width(node: &DiffNode) -> usize {
-    let mut max = node.key.as_deref().unwrap_or(&node.tag).len();
-    for c in &node.children {
-        let child_max = compute_max_width(c);
-        if child_max > max { max = child_max; }
+fn paint(s: &str, color: Color, enabled: bool) -> String {
+    if enabled { s.with(color).to_string() } else { s.to_string() }
+}
+
+/// ` [warning]` after a node that has a severity, empty otherwise.
+fn severity_suffix(node: &DiffNode, color: bool) -> String {
+    match node.severity {
+        Some(severity) => {
+            let c = match severity {
+                Severity::Error => Color::Red,
+                Severity::Warning => Color::Yellow,
+                Severity::Info => Color::Cyan,
+            };
+            format!(" {}", paint(&format!("[{}]", severity), c, color))
+        }
+        None => String::new(),
     }
-    max
 }
 
-fn line_values(node: &DiffNode) -> (String, String) {
-    match node.status {
-        NodeStatus::Added => ("".to_string(), node.key.as_deref().unwrap_or(&node.tag).to_string()),
-        NodeStatus::Removed => (node.key.as_deref().unwrap_or(&node.tag).to_string(), "".to_string()),
-        NodeStatus::Unchanged => {
-            let k = node.key.as_deref().unwrap_or(&node.tag).to_string();
-            (k.clone(), k)
+/// A child row to render, or a run of unchanged siblings left out.
+enum Shown<'a> {
+    Node(&'a DiffNode),
+    Skipped(usize),
+}
+
+/// Children of `node` to render. With `context` set, only changed children are
+/// shown, plus up to `context` unchanged siblings on either side of each; unchanged
+/// elements are not expanded.
+fn shown_children(node: &DiffNode, context: Option<usize>) -> Vec<Shown<'_>> {
+    let Some(context) = context else {
+        return node.children.iter().map(Shown::Node).collect();
+    };
+    if !node.has_changes() {
+        return Vec::new();
+    }
+    let changed: Vec<usize> = node.children.iter().enumerate().filter(|(_, c)| c.has_changes()).map(|(i, _)| i).collect();
+    let near_change = |i: usize| changed.iter().any(|&c| c.abs_diff(i) <= context);
+    let mut shown = Vec::new();
+    for (i, c) in node.children.iter().enumerate() {
+        if near_change(i) {
+            shown.push(Shown::Node(c));
+        } else if let Some(Shown::Skipped(n)) = shown.last_mut() {
+            *n += 1;
+        } else {
+            shown.push(Shown::Skipped(1));
         }
-        NodeStatus::Changed => {
-            if let Some(k) = &node.key { return (k.clone(), k.clone()); }
-            // Try to extract a left and right candidate from children
-            let mut left = String::new();
-            let mut right = String::new();
-            for c in &node.children {
-                if left.is_empty() && c.status != NodeStatus::Added {
-                    left = c.key.as_deref().unwrap_or(&c.tag).to_string();
-                }
-                if right.is_empty() && c.status != NodeStatus::Removed {
-                    right = c.key.as_deref().unwrap_or(&c.tag).to_string();
-                }
+    }
+    shown
+}
+
+fn skipped_label(n: usize, color: bool) -> String {
+    paint(&format!("… {} unchanged", n), Color::DarkGrey, color)
+}
+
+/// Indented tree with a `+`/`-`/`~` marker per node.
+#[derive(Debug, Clone, Default)]
+pub struct TreeRenderer {
+    pub color: bool,
+    /// Show only changes and this many unchanged siblings around each; `None` shows everything
+    pub context: Option<usize>,
+}
+
+impl TreeRenderer {
+    fn render_node(&self, node: &DiffNode, indent: usize, out: &mut dyn Write) -> io::Result<()> {
+        let prefix = match node.status {
+            NodeStatus::Added => paint("+", Color::Green, self.color),
+            NodeStatus::Removed => paint("-", Color::Red, self.color),
+            NodeStatus::Changed => paint("~", Color::Yellow, self.color),
+            NodeStatus::Unchanged => " ".to_string(),
+        };
+
+        let mut name = node.key.as_deref().unwrap_or(&node.tag).to_string();
+        if let Some(new_name) = node.renamed_to() {
+            name = format!("{} -> {}", name, new_name);
+        }
+        let indent_str = "  ".repeat(indent);
+        writeln!(out, "{}{} {}{}", indent_str, prefix, name, severity_suffix(node, self.color))?;
+
+        for shown in shown_children(node, self.context) {
+            match shown {
+                Shown::Node(c) => self.render_node(c, indent + 1, out)?,
+                Shown::Skipped(n) => writeln!(out, "{}  {}", "  ".repeat(indent + 1), skipped_label(n, self.color))?,
             }
-            if left.is_empty() && right.is_empty() { (node.tag.clone(), node.tag.clone()) }
-            else { (left, right) }
+        }
+        Ok(())
+    }
+}
+
+impl Renderer for TreeRenderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        self.render_node(root, 0, out)
+    }
+}
+
+/// Layout width when stdout is not a terminal and `COLUMNS` is not set
+const DEFAULT_WIDTH: usize = 120;
+
+/// Separator between the columns, around the change marker
+const SEPARATOR: &str = " │ ";
+
+/// Narrowest column the side-by-side layout goes down to
+const MIN_COLUMN_WIDTH: usize = 10;
+
+/// Width to lay out output for: the terminal on stdout, else `$COLUMNS`, else 120.
+pub fn terminal_width() -> usize {
+    if io::stdout().is_terminal() {
+        if let Ok((columns, _)) = crossterm::terminal::size() {
+            return columns as usize;
         }
     }
+    std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(DEFAULT_WIDTH)
 }
 
-fn render_node_side(node: &DiffNode, indent: usize, left_width: usize) {
-    let indent_str = "  ".repeat(indent);
-    let (l, r) = line_values(node);
+/// Display width of `s` in terminal columns, not counting ANSI escape sequences.
+pub fn display_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' && chars.peek() == Some(&'[') {
+            // CSI sequence: parameters up to a final byte in @..~
+            chars.next();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) { break; }
+            }
+            continue;
+        }
+        width += c.width().unwrap_or(0);
+    }
+    width
+}
 
-    // If node has no explicit key and its single child has the same key on both sides,
-    // skip printing the parent row to avoid duplicate rows and render the child directly.
-    if node.key.is_none() && node.children.len() == 1 {
-        let child = &node.children[0];
-        if let (Some(ck), Some(_)) = (child.key.as_deref(), Some(())) {
-            if ck == l && ck == r {
-                render_node_side(child, indent, left_width);
-                return;
+/// Split plain text into lines of at most `width` columns, breaking at spaces where
+/// possible.
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in s.split(' ') {
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && line.width() + 1 + word.width() <= width {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(String::new());
+        }
+        // words longer than a line are broken anywhere
+        for c in word.chars() {
+            let line = lines.last_mut().unwrap();
+            if line.width() + c.width().unwrap_or(0) > width && !line.is_empty() {
+                lines.push(String::new());
             }
+            lines.last_mut().unwrap().push(c);
         }
     }
+    lines
+}
 
-    // Prepare left and right cell content with indentation
-    let left_cell = if l.is_empty() { "".to_string() } else { format!("{}{}", indent_str, l) };
-    let right_cell = if r.is_empty() { "".to_string() } else { format!("{}{}", indent_str, r) };
+/// Shorten plain text to at most `width` columns, marking the cut with `…`.
+fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width { break; }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
 
-    // Apply colors: removed -> red (left), added -> green (right), changed -> yellow marker in middle
-    let left_repr = if node.status == NodeStatus::Removed { left_cell.red().to_string() } else { left_cell.clone() };
-    let right_repr = if node.status == NodeStatus::Added { right_cell.green().to_string() } else { right_cell.clone() };
+fn pad(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(display_width(s))))
+}
 
-    let mid = match node.status {
-        NodeStatus::Changed => " ~ ".yellow().to_string(),
-        NodeStatus::Added => "   ".to_string(),
-        NodeStatus::Removed => "   ".to_string(),
-        NodeStatus::Unchanged => "   ".to_string(),
+/// Label of the element on each side, followed by its changed values on that side,
+/// e.g. `Speed = 120 @T=2024`.
+pub(crate) fn side_labels(node: &DiffNode) -> (String, String) {
+    let label = |n: &DiffNode| n.key.clone().unwrap_or_else(|| n.tag.clone());
+    let (mut left, mut right) = match node.status {
+        NodeStatus::Added => (String::new(), label(node)),
+        NodeStatus::Removed => (label(node), String::new()),
+        // an element replaced by another one: each side is named by its own element
+        _ => match node.replaced() {
+            Some((l, r)) => (label(l), label(r)),
+            None => (label(node), label(node)),
+        },
     };
+    // content values first, then attributes
+    let (attributes, content): (Vec<_>, Vec<_>) = node.changes.iter().partition(|c| c.what.starts_with('@'));
+    if let Some(new_name) = node.renamed_to() {
+        right = new_name.to_string();
+    }
+    for change in content.into_iter().chain(attributes).filter(|c| c.what != "short-name") {
+        for (cell, value) in [(&mut left, &change.left), (&mut right, &change.right)] {
+            let Some(value) = value else { continue };
+            match change.what.as_str() {
+                "text" => cell.push_str(&format!(" = {}", value)),
+                attr if attr.starts_with('@') => cell.push_str(&format!(" {}={}", attr, value)),
+                kind => cell.push_str(&format!(" {}: {}", kind, value)),
+            }
+        }
+    }
+    (left, right)
+}
 
-    println!("{:<width$}{}{}", left_repr, mid, right_repr, width = left_width + 2);
+/// Two columns with the left and right element, changed values on each side and a
+/// change marker in between. Columns share the available width; long cells are
+/// truncated, or wrapped onto further rows.
+#[derive(Debug, Clone, Default)]
+pub struct SideBySideRenderer {
+    pub color: bool,
+    /// Show only changes and this many unchanged siblings around each; `None` shows everything
+    pub context: Option<usize>,
+    /// Total width in terminal columns, see [`terminal_width`]
+    pub width: usize,
+    /// Wrap long cells instead of truncating them
+    pub wrap: bool,
+}
 
-    for c in &node.children {
-        render_node_side(c, indent + 1, left_width);
+impl SideBySideRenderer {
+    fn column_width(&self) -> usize {
+        (self.width.saturating_sub(2 * display_width(SEPARATOR) + 1) / 2).max(MIN_COLUMN_WIDTH)
     }
-}
 
-pub fn render_side_by_side(root: &DiffNode) -> Result<()> {
-    // compute a reasonable left column width
-    let max = compute_max_width(root);
-    let left_width = std::cmp::min(40, max) + 4; // add padding
-    println!("{:<width$} | {}", "LEFT", "RIGHT", width = left_width + 2);
-    println!("{:-<width$}-+-{:-<right$}", "", "", width = left_width + 2, right = 20);
+    /// Lay out one cell: `text` in `color` after `indent`, followed by an already
+    /// coloured `suffix`. Wrapped lines are indented one level further.
+    fn cell(&self, indent: &str, text: &str, color: Option<Color>, suffix: &str) -> Vec<String> {
+        let column = self.column_width();
+        let paint_text = |t: &str| match color {
+            Some(c) => paint(t, c, self.color),
+            None => t.to_string(),
+        };
+        if !self.wrap {
+            let room = column.saturating_sub(display_width(suffix));
+            return vec![format!("{}{}", paint_text(&truncate(&format!("{}{}", indent, text), room)), suffix)];
+        }
+        let hanging = format!("{}  ", indent);
+        let room = column.saturating_sub(hanging.width()).max(1);
+        let mut lines: Vec<String> = wrap(text, room).iter().enumerate()
+            .map(|(i, l)| paint_text(&format!("{}{}", if i == 0 { indent } else { &hanging }, l)))
+            .collect();
+        if !suffix.is_empty() {
+            let last = lines.last_mut().unwrap();
+            if display_width(last) + display_width(suffix) <= column {
+                last.push_str(suffix);
+            } else {
+                lines.push(format!("{}{}", hanging, suffix.trim_start()));
+            }
+        }
+        lines
+    }
+
+    fn write_row(&self, left: &[String], marker: &str, right: &[String], out: &mut dyn Write) -> io::Result<()> {
+        let column = self.column_width();
+        for i in 0..left.len().max(right.len()) {
+            let l = left.get(i).map_or("", String::as_str);
+            let r = right.get(i).map_or("", String::as_str);
+            let m = if i == 0 { marker.to_string() } else { " ".to_string() };
+            let line = format!("{}{}{}{}{}", pad(l, column), SEPARATOR, m, SEPARATOR, r);
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+
+    fn render_node(&self, node: &DiffNode, indent: usize, out: &mut dyn Write) -> io::Result<()> {
+        let indent_str = "  ".repeat(indent);
+        let (l, r) = side_labels(node);
+        let (marker, left_color, right_color) = match node.status {
+            NodeStatus::Added => (paint("+", Color::Green, self.color), None, Some(Color::Green)),
+            NodeStatus::Removed => (paint("-", Color::Red, self.color), Some(Color::Red), None),
+            NodeStatus::Changed => (paint("~", Color::Yellow, self.color), None, None),
+            NodeStatus::Unchanged => (" ".to_string(), None, None),
+        };
+        // the severity goes with the element: on the left if it was removed
+        let severity = severity_suffix(node, self.color);
+        let (left_severity, right_severity) = if r.is_empty() { (severity.as_str(), "") } else { ("", severity.as_str()) };
+        let left = if l.is_empty() { Vec::new() } else { self.cell(&indent_str, &l, left_color, left_severity) };
+        let right = if r.is_empty() { Vec::new() } else { self.cell(&indent_str, &r, right_color, right_severity) };
+        self.write_row(&left, &marker, &right, out)?;
+
+        for shown in shown_children(node, self.context) {
+            match shown {
+                Shown::Node(c) => self.render_node(c, indent + 1, out)?,
+                Shown::Skipped(n) => {
+                    let label = vec![format!("{}{}", "  ".repeat(indent + 1), skipped_label(n, self.color))];
+                    self.write_row(&label, " ", &label, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-    render_node_side(root, 0, left_width);
-    Ok(())
+impl Renderer for SideBySideRenderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        let column = self.column_width();
+        self.write_row(&["LEFT".to_string()], " ", &["RIGHT".to_string()], out)?;
+        let rule = "─".repeat(column);
+        writeln!(out, "{}─┼───┼─{}", rule, rule)?;
+        self.render_node(root, 0, out)
+    }
 }
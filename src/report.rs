@@ -0,0 +1,257 @@
+use crate::diff::{Change, ChangeKind, DiffNode, ValueChange};
+use crate::input::member_spec;
+use crate::parse::escape;
+use crate::policy::Severity;
+use crate::render::Renderer;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The changed element: its AUTOSAR path if it has one, otherwise the path it is in.
+fn subject(node: &DiffNode) -> String {
+    match (node.is_identifiable(), node.path.is_empty()) {
+        (true, _) => format!("{} {}", node.tag, node.path),
+        (false, true) => node.tag.clone(),
+        (false, false) => format!("{} in {}", node.tag, node.path),
+    }
+}
+
+fn quoted(value: &Option<String>) -> String {
+    value.as_ref().map_or_else(|| "<none>".to_string(), |v| format!("{:?}", v))
+}
+
+fn describe_value(c: &ValueChange) -> String {
+    format!("{} {} -> {}", c.what, quoted(&c.left), quoted(&c.right))
+}
+
+/// One-line description of a change, e.g. `changed TYPE-TREF in /Pkg/If/Speed: text "A" -> "B"`.
+pub(crate) fn describe(change: &Change) -> String {
+    let mut text = format!("{} {}", change.kind, subject(change.node));
+    if change.kind == ChangeKind::Changed {
+        let values: Vec<String> = change.node.changes.iter().map(describe_value).collect();
+        text.push_str(": ");
+        text.push_str(&values.join(", "));
+    }
+    text
+}
+
+/// `path` as a URI: absolute paths become `file://` URIs, relative ones stay relative
+/// references. Characters not allowed in a URI path are percent-encoded.
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let bytes = path.as_bytes();
+    let drive = bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'/';
+    let mut uri = String::with_capacity(path.len() + 8);
+    if drive {
+        uri.push_str("file:///");
+    } else if path.starts_with('/') {
+        uri.push_str("file://");
+    }
+    for &b in bytes {
+        // a colon is only kept after a drive letter, so a relative path never looks like a scheme
+        if b.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=@".contains(&b) || (b == b':' && drive) {
+            uri.push(char::from(b));
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+/// SARIF 2.1.0 log with one result per change, located at the element in the right
+/// input (left for removals).
+#[derive(Debug, Clone, Default)]
+pub struct SarifRenderer {
+    /// Left input as given on the command line
+    pub left: String,
+    /// Right input as given on the command line
+    pub right: String,
+}
+
+impl SarifRenderer {
+    fn result(&self, change: &Change) -> serde_json::Value {
+        let (input, pos) = match change.kind {
+            ChangeKind::Removed => (&self.left, change.node.left_pos),
+            ChangeKind::Added | ChangeKind::Changed => (&self.right, change.node.right_pos),
+        };
+        let location = match change.file {
+            Some(key) => member_spec(input, key),
+            None => input.clone(),
+        };
+        let mut physical = json!({ "artifactLocation": { "uri": file_uri(&location) } });
+        if let Some(pos) = pos {
+            physical["region"] = json!({ "startLine": pos.line, "startColumn": pos.column });
+        }
+        let mut location = json!({ "physicalLocation": physical });
+        if !change.node.path.is_empty() {
+            location["logicalLocations"] = json!([{ "fullyQualifiedName": change.node.path, "kind": "element" }]);
+        }
+        let level = match change.node.severity {
+            Some(Severity::Error) => "error",
+            Some(Severity::Warning) | None => "warning",
+            Some(Severity::Info) => "note",
+        };
+        json!({
+            "ruleId": change.kind.to_string(),
+            "level": level,
+            "message": { "text": describe(change) },
+            "locations": [location],
+        })
+    }
+}
+
+impl Renderer for SarifRenderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        let rule = |kind: ChangeKind, text: &str| json!({ "id": kind.to_string(), "shortDescription": { "text": text } });
+        let results: Vec<_> = root.collect_changes().iter().map(|c| self.result(c)).collect();
+        let log = json!({
+            "$schema": SARIF_SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": { "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        rule(ChangeKind::Added, "Element added"),
+                        rule(ChangeKind::Removed, "Element removed"),
+                        rule(ChangeKind::Changed, "Element values changed"),
+                    ],
+                }},
+                "results": results,
+            }],
+        });
+        serde_json::to_writer_pretty(&mut *out, &log)?;
+        writeln!(out)
+    }
+}
+
+/// JUnit XML with one testcase per changed identifiable element (the nearest element
+/// with a SHORT-NAME around each change) and one testsuite per compared file.
+#[derive(Debug, Clone, Default)]
+pub struct JunitRenderer {
+    pub left: String,
+    pub right: String,
+    /// Testcases fail for changes of at least this severity; any change fails if unset
+    pub fail_on: Option<Severity>,
+}
+
+struct TestCase<'a> {
+    path: &'a str,
+    changes: Vec<Change<'a>>,
+}
+
+impl JunitRenderer {
+    fn fails(&self, change: &Change) -> bool {
+        match self.fail_on {
+            Some(threshold) => change.node.severity >= Some(threshold),
+            None => true,
+        }
+    }
+
+    fn write_case(&self, case: &TestCase, out: &mut dyn Write) -> io::Result<()> {
+        let (classname, name) = match case.path.rsplit_once('/') {
+            Some((parent, name)) if !name.is_empty() => (parent, name),
+            _ => ("", "/"),
+        };
+        writeln!(out, "    <testcase classname=\"{}\" name=\"{}\">", escape(classname), escape(name))?;
+        let details: Vec<String> = case.changes.iter()
+            .map(|c| match c.node.severity {
+                Some(s) => format!("[{}] {}", s, describe(c)),
+                None => describe(c),
+            })
+            .collect();
+        let failing: Vec<&Change> = case.changes.iter().filter(|c| self.fails(c)).collect();
+        if let Some(first) = failing.first() {
+            let kind = failing.iter().filter_map(|c| c.node.severity).max().map_or("change".to_string(), |s| s.to_string());
+            writeln!(out, "      <failure type=\"{}\" message=\"{}\">{}</failure>", kind, escape(&describe(first)), escape(&details.join("\n")))?;
+        } else {
+            writeln!(out, "      <system-out>{}</system-out>", escape(&details.join("\n")))?;
+        }
+        writeln!(out, "    </testcase>")
+    }
+}
+
+impl Renderer for JunitRenderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        // group changes by file, then by identifiable element, in document order
+        let mut suites: Vec<(Option<&str>, Vec<TestCase>)> = Vec::new();
+        // index of each testcase of the current suite by path
+        let mut case_of: HashMap<&str, usize> = HashMap::new();
+        for change in root.collect_changes() {
+            if suites.last().map(|(f, _)| *f != change.file).unwrap_or(true) {
+                suites.push((change.file, Vec::new()));
+                case_of.clear();
+            }
+            let cases = &mut suites.last_mut().unwrap().1;
+            let path = change.node.path.as_str();
+            let i = *case_of.entry(path).or_insert_with(|| {
+                cases.push(TestCase { path, changes: Vec::new() });
+                cases.len() - 1
+            });
+            cases[i].changes.push(change);
+        }
+        let failures = |cases: &[TestCase]| cases.iter().filter(|c| c.changes.iter().any(|ch| self.fails(ch))).count();
+        let total_tests: usize = suites.iter().map(|(_, c)| c.len()).sum();
+        let total_failures: usize = suites.iter().map(|(_, c)| failures(c)).sum();
+
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(out, "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">", env!("CARGO_PKG_NAME"), total_tests, total_failures)?;
+        for (file, cases) in &suites {
+            let name = match file {
+                Some(key) => key.to_string(),
+                None => format!("{} vs {}", self.left, self.right),
+            };
+            writeln!(out, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">", escape(&name), cases.len(), failures(cases))?;
+            for case in cases {
+                self.write_case(case, out)?;
+            }
+            writeln!(out, "  </testsuite>")?;
+        }
+        writeln!(out, "</testsuites>")
+    }
+}
+
+/// One line per change, addressed by AUTOSAR path, for grepping and sorting:
+/// `A /Pkg/NewIf`, `D /Pkg/OldSignal`, `R /Pkg/A -> /Pkg/B` and
+/// `M /Pkg/Swc/run1/TEXT text "10" -> "20"`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathsRenderer;
+
+/// The element's AUTOSAR path, or its tag under the nearest path, prefixed with the
+/// file it is in for collections.
+fn designator(change: &Change) -> String {
+    let node = change.node;
+    let identifiable = node.key.is_some() && node.path.rsplit('/').next() == node.key.as_deref();
+    let path = if identifiable { node.path.clone() } else { format!("{}/{}", node.path, node.tag) };
+    match change.file {
+        Some(file) => format!("{}:{}", file, path),
+        None => path,
+    }
+}
+
+impl Renderer for PathsRenderer {
+    fn render(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        for change in root.collect_changes() {
+            let severity = change.node.severity.map_or_else(String::new, |s| format!(" [{}]", s));
+            let at = designator(&change);
+            match change.kind {
+                ChangeKind::Added => writeln!(out, "A {}{}", at, severity)?,
+                ChangeKind::Removed => writeln!(out, "D {}{}", at, severity)?,
+                ChangeKind::Changed => {
+                    for value in &change.node.changes {
+                        match (value.what.as_str(), &value.right) {
+                            ("short-name", Some(name)) => {
+                                let parent = at.rsplit_once('/').map_or("", |(p, _)| p);
+                                writeln!(out, "R {} -> {}/{}{}", at, parent, name, severity)?;
+                            }
+                            _ => writeln!(out, "M {} {}{}", at, describe_value(value), severity)?,
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
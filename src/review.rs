@@ -0,0 +1,254 @@
+use crate::diff::{Change, DiffNode, NodeStatus};
+use crate::error::{Error, Result};
+use crate::input;
+use crate::report::describe;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// What a reviewer recorded for one element.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mark {
+    #[serde(default)]
+    pub reviewed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Mark {
+    fn is_empty(&self) -> bool {
+        !self.reviewed && self.note.is_none()
+    }
+}
+
+/// An input as recorded in the sidecar file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct InputRecord {
+    input: String,
+    sha256: String,
+}
+
+/// The marks made for one pair of inputs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    left: InputRecord,
+    right: InputRecord,
+    #[serde(default)]
+    marks: BTreeMap<String, Mark>,
+}
+
+/// The sidecar file: reviews of any number of input pairs, told apart by the hashes
+/// of the inputs, so that marks only come back for the inputs they were made on.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct Sidecar {
+    #[serde(default)]
+    reviews: Vec<Entry>,
+}
+
+impl Sidecar {
+    fn load(path: &Path) -> Result<Self> {
+        let name = path.to_string_lossy();
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| Error::input(&name, format_args!("invalid review file: {}", e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Sidecar::default()),
+            Err(e) => Err(Error::io(&name, e)),
+        }
+    }
+}
+
+/// SHA-256 of an input as hex. A directory or archive is hashed over the relative
+/// paths and contents of its ARXML documents.
+pub fn input_hash(spec: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    if input::is_collection(spec) {
+        for m in input::read_collection(spec)? {
+            hasher.update(m.key.as_bytes());
+            hasher.update((m.data.len() as u64).to_le_bytes());
+            hasher.update(&m.data);
+        }
+    } else {
+        hasher.update(input::read_input(spec)?);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Default sidecar file for a comparison: next to the right input, or the archive
+/// it is in.
+pub fn default_review_file(right: &str) -> PathBuf {
+    let file = right.split("!/").next().unwrap_or(right).trim_end_matches(['/', '\\']);
+    PathBuf::from(format!("{}.review.json", file))
+}
+
+/// A change together with the key its marks are kept under.
+pub struct Reviewable<'a> {
+    pub key: String,
+    /// Child indices from the root to the node
+    pub indices: Vec<usize>,
+    pub change: Change<'a>,
+}
+
+/// Keys identify elements across sessions: the AUTOSAR path of elements with a
+/// SHORT-NAME, otherwise the key of the parent and the tag, numbered as `TAG[2]` when
+/// the parent has several such children. Keys in a set of documents start with the
+/// file, as in `ECU.arxml:/Pkg/Speed`.
+fn child_key(parent: &DiffNode, parent_key: &str, file: Option<&str>, index: usize) -> String {
+    let node = &parent.children[index];
+    if node.tag == "FILE" && parent.tag == "FILES" {
+        return format!("{}:", node.key.as_deref().unwrap_or(""));
+    }
+    if node.is_identifiable() {
+        return format!("{}{}", file.map_or_else(String::new, |f| format!("{}:", f)), node.path);
+    }
+    let mut key = format!("{}/{}", parent_key, node.tag);
+    let same_tag = |c: &&DiffNode| c.tag == node.tag && !c.is_identifiable();
+    if parent.children.iter().filter(same_tag).count() > 1 {
+        let nth = parent.children[..index].iter().filter(same_tag).count() + 1;
+        key.push_str(&format!("[{}]", nth));
+    }
+    key
+}
+
+/// The file a child of `parent` is in, given the file `parent` is in.
+fn child_file<'a>(parent: &DiffNode, child: &'a DiffNode, file: Option<&'a str>) -> Option<&'a str> {
+    if child.tag == "FILE" && parent.tag == "FILES" { child.key.as_deref() } else { file }
+}
+
+/// The review key of the node reached from `root` by the child `indices`.
+pub fn review_key(root: &DiffNode, indices: &[usize]) -> String {
+    let mut key = format!("/{}", root.tag);
+    let mut file = None;
+    let mut node = root;
+    for &i in indices {
+        key = child_key(node, &key, file, i);
+        file = child_file(node, &node.children[i], file);
+        node = &node.children[i];
+    }
+    key
+}
+
+/// Every change in the diff with its review key, in document order.
+pub fn reviewables(root: &DiffNode) -> Vec<Reviewable<'_>> {
+    fn rec<'a>(node: &'a DiffNode, parent: Option<NodeStatus>, key: String, file: Option<&'a str>, indices: &mut Vec<usize>, out: &mut Vec<Reviewable<'a>>) {
+        if let Some(kind) = node.change_kind(parent) {
+            out.push(Reviewable { key: key.clone(), indices: indices.clone(), change: Change { node, kind, file } });
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            indices.push(i);
+            rec(child, Some(node.status), child_key(node, &key, file, i), child_file(node, child, file), indices, out);
+            indices.pop();
+        }
+    }
+    let mut out = Vec::new();
+    rec(root, None, format!("/{}", root.tag), None, &mut Vec::new(), &mut out);
+    out
+}
+
+/// The review of one pair of inputs, kept in a sidecar file together with the
+/// reviews of other pairs.
+#[derive(Debug, Clone)]
+pub struct Review {
+    path: PathBuf,
+    left: InputRecord,
+    right: InputRecord,
+    marks: BTreeMap<String, Mark>,
+}
+
+impl Review {
+    /// The review of the inputs `left` and `right` kept in `path`, with the marks made
+    /// before on inputs with the same contents.
+    pub fn open(path: impl Into<PathBuf>, left: &str, right: &str) -> Result<Self> {
+        let path = path.into();
+        let left = InputRecord { input: left.to_string(), sha256: input_hash(left)? };
+        let right = InputRecord { input: right.to_string(), sha256: input_hash(right)? };
+        let marks = Sidecar::load(&path)?.reviews.into_iter()
+            .find(|e| e.left.sha256 == left.sha256 && e.right.sha256 == right.sha256)
+            .map(|e| e.marks)
+            .unwrap_or_default();
+        Ok(Review { path, left, right, marks })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Marks by review key, see [`review_key`].
+    pub fn marks(&self) -> &BTreeMap<String, Mark> {
+        &self.marks
+    }
+
+    pub fn is_reviewed(&self, key: &str) -> bool {
+        self.marks.get(key).is_some_and(|m| m.reviewed)
+    }
+
+    pub fn note(&self, key: &str) -> Option<&str> {
+        self.marks.get(key).and_then(|m| m.note.as_deref())
+    }
+
+    fn update(&mut self, key: &str, f: impl FnOnce(&mut Mark)) {
+        let mark = self.marks.entry(key.to_string()).or_default();
+        f(mark);
+        if mark.is_empty() {
+            self.marks.remove(key);
+        }
+    }
+
+    pub fn set_reviewed(&mut self, key: &str, reviewed: bool) {
+        self.update(key, |m| m.reviewed = reviewed);
+    }
+
+    /// Set the note on an element; an empty note removes it.
+    pub fn set_note(&mut self, key: &str, note: &str) {
+        let note = note.trim();
+        self.update(key, |m| m.note = (!note.is_empty()).then(|| note.to_string()));
+    }
+
+    /// Number of `changes` reviewed, and of all of them.
+    pub fn progress(&self, changes: &[Reviewable]) -> (usize, usize) {
+        (changes.iter().filter(|c| self.is_reviewed(&c.key)).count(), changes.len())
+    }
+
+    /// Write the marks to the sidecar file, keeping the reviews of other inputs in it.
+    pub fn save(&self) -> Result<()> {
+        let name = self.path.to_string_lossy();
+        let mut sidecar = Sidecar::load(&self.path)?;
+        sidecar.reviews.retain(|e| e.left.sha256 != self.left.sha256 || e.right.sha256 != self.right.sha256);
+        if !self.marks.is_empty() {
+            sidecar.reviews.push(Entry { left: self.left.clone(), right: self.right.clone(), marks: self.marks.clone() });
+        }
+        let json = serde_json::to_string_pretty(&sidecar).expect("review file is serializable");
+        std::fs::write(&self.path, json + "\n").map_err(|e| Error::io(&name, e))
+    }
+
+    /// A plain text report listing every change of `root` as reviewed (`[x]`) or not
+    /// (`[ ]`) with its note, followed by the notes on elements that are not changes.
+    pub fn write_report(&self, root: &DiffNode, out: &mut dyn Write) -> io::Result<()> {
+        let changes = reviewables(root);
+        let (reviewed, total) = self.progress(&changes);
+        writeln!(out, "Review of {} and {}", self.left.input, self.right.input)?;
+        writeln!(out, "{}/{} changes reviewed", reviewed, total)?;
+        writeln!(out)?;
+        for c in &changes {
+            let check = if self.is_reviewed(&c.key) { "x" } else { " " };
+            let file = c.change.file.map_or_else(String::new, |f| format!("{}: ", f));
+            let severity = c.change.node.severity.map_or_else(String::new, |s| format!(" [{}]", s));
+            writeln!(out, "[{}] {}{}{}", check, file, describe(&c.change), severity)?;
+            if let Some(note) = self.note(&c.key) {
+                writeln!(out, "    {}", note)?;
+            }
+        }
+        let other: Vec<(&String, &str)> = self.marks.iter()
+            .filter(|(key, _)| !changes.iter().any(|c| &c.key == *key))
+            .filter_map(|(key, m)| Some((key, m.note.as_deref()?)))
+            .collect();
+        if !other.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "Other notes:")?;
+            for (key, note) in other {
+                writeln!(out, "{}: {}", key, note)?;
+            }
+        }
+        Ok(())
+    }
+}
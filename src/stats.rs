@@ -0,0 +1,186 @@
+use crate::diff::{ChangeKind, DiffNode, NodeStatus};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Numbers of changes of each kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// Elements removed in one place and added with the same tag and key in another
+    pub moved: usize,
+}
+
+impl Counts {
+    /// Changes in the diff, counted as by [`Stats::of`].
+    pub fn of(root: &DiffNode) -> Counts {
+        let mut entries = Vec::new();
+        collect(root, None, None, "", &mut entries);
+        let mut counts = Counts::default();
+        for (tally, _) in tallies(&entries) {
+            counts.tally(tally);
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.added + self.removed + self.changed + self.moved
+    }
+
+    fn tally(&mut self, tally: Tally) {
+        match tally {
+            Tally::Added => self.added += 1,
+            Tally::Removed => self.removed += 1,
+            Tally::Changed => self.changed += 1,
+            Tally::Moved => self.moved += 1,
+        }
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} added, {} removed, {} changed, {} moved", self.added, self.removed, self.changed, self.moved)
+    }
+}
+
+/// Overview of a diff: what changed where, how large the inputs are and how long
+/// the comparison took. [`Stats::of`] fills in the changes only.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub totals: Counts,
+    /// Changes per element tag, most changed first
+    pub by_tag: Vec<(String, Counts)>,
+    /// Changes per innermost AR-PACKAGE, by AUTOSAR path, most changed first
+    pub packages: Vec<(String, Counts)>,
+    /// Elements in the left and right inputs, SHORT-NAMEs included
+    pub left_elements: usize,
+    pub right_elements: usize,
+    pub parse_time: Duration,
+    pub diff_time: Duration,
+}
+
+#[derive(Clone, Copy)]
+enum Tally {
+    Added,
+    Removed,
+    Changed,
+    Moved,
+}
+
+struct Entry<'a> {
+    node: &'a DiffNode,
+    kind: ChangeKind,
+    package: String,
+}
+
+fn collect<'a>(node: &'a DiffNode, parent: Option<NodeStatus>, file: Option<&'a str>, package: &str, out: &mut Vec<Entry<'a>>) {
+    let file = if node.tag == "FILE" && parent.is_some() { node.key.as_deref() } else { file };
+    // a package is counted in its parent package, its content in the package itself
+    if let Some(kind) = node.change_kind(parent) {
+        out.push(Entry { node, kind, package: package.to_string() });
+    }
+    let own;
+    let package = if node.tag == "AR-PACKAGE" && node.key.is_some() {
+        own = match file {
+            Some(file) => format!("{}:{}", file, node.path),
+            None => node.path.clone(),
+        };
+        &own
+    } else {
+        package
+    };
+    for c in &node.children {
+        collect(c, Some(node.status), file, package, out);
+    }
+}
+
+/// Counts by name, most changes first.
+fn ranked(counts: HashMap<&str, Counts>) -> Vec<(String, Counts)> {
+    let mut list: Vec<_> = counts.into_iter().map(|(name, counts)| (name.to_string(), counts)).collect();
+    list.sort_by(|(a, ca), (b, cb)| cb.total().cmp(&ca.total()).then_with(|| a.cmp(b)));
+    list
+}
+
+/// How each change is counted, with the entry it is counted at. A removed and an
+/// added element with the same tag and key are one move, counted where the element
+/// ends up.
+fn tallies<'e, 'a>(entries: &'e [Entry<'a>]) -> Vec<(Tally, &'e Entry<'a>)> {
+    let mut out = Vec::with_capacity(entries.len());
+    // added or removed entries still waiting for their counterpart, oldest first; all
+    // entries waiting under one tag and key are of the same kind
+    let mut unpaired: HashMap<(&str, &str), VecDeque<usize>> = HashMap::new();
+    for (i, e) in entries.iter().enumerate() {
+        let tally = match e.kind {
+            ChangeKind::Added => Tally::Added,
+            ChangeKind::Removed => Tally::Removed,
+            ChangeKind::Changed => {
+                out.push((Tally::Changed, e));
+                continue;
+            }
+        };
+        let Some(key) = e.node.key.as_deref() else {
+            out.push((tally, e));
+            continue;
+        };
+        let waiting = unpaired.entry((&e.node.tag, key)).or_default();
+        match waiting.front() {
+            Some(&j) if entries[j].kind != e.kind => {
+                waiting.pop_front();
+                out.push((Tally::Moved, if e.kind == ChangeKind::Added { e } else { &entries[j] }));
+            }
+            _ => waiting.push_back(i),
+        }
+    }
+    for i in unpaired.into_values().flatten() {
+        let e = &entries[i];
+        out.push((if e.kind == ChangeKind::Added { Tally::Added } else { Tally::Removed }, e));
+    }
+    out
+}
+
+impl Stats {
+    pub fn of(root: &DiffNode) -> Stats {
+        let mut entries = Vec::new();
+        collect(root, None, None, "", &mut entries);
+
+        let mut stats = Stats::default();
+        let mut by_tag: HashMap<&str, Counts> = HashMap::new();
+        let mut packages: HashMap<&str, Counts> = HashMap::new();
+        for (tally, at) in tallies(&entries) {
+            stats.totals.tally(tally);
+            by_tag.entry(&at.node.tag).or_default().tally(tally);
+            if !at.package.is_empty() {
+                packages.entry(&at.package).or_default().tally(tally);
+            }
+        }
+        stats.by_tag = ranked(by_tag);
+        stats.packages = ranked(packages);
+        stats
+    }
+
+    /// Write the statistics as text, listing at most `top` tags and packages.
+    pub fn write_report(&self, top: usize, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "Elements: {} left, {} right", self.left_elements, self.right_elements)?;
+        writeln!(out, "Changes:  {}", self.totals)?;
+        writeln!(out, "Timing:   parsed in {:.1?}, diffed in {:.1?}", self.parse_time, self.diff_time)?;
+        for (title, list) in [("Changes by tag", &self.by_tag), ("Most changed packages", &self.packages)] {
+            if list.is_empty() {
+                continue;
+            }
+            writeln!(out)?;
+            writeln!(out, "{}:", title)?;
+            let shown = &list[..list.len().min(top)];
+            let width = shown.iter().map(|(n, _)| n.chars().count()).max().unwrap_or(0);
+            for (name, counts) in shown {
+                writeln!(out, "  {:<width$}  {:>5}  ({})", name, counts.total(), counts, width = width)?;
+            }
+            if list.len() > shown.len() {
+                writeln!(out, "  … {} more", list.len() - shown.len())?;
+            }
+        }
+        Ok(())
+    }
+}
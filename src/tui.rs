@@ -1,265 +1,1294 @@
-use std::collections::HashSet;
+//! The interactive tree view.
+//!
+//! Its state lives in [`App`], which takes key events through [`App::handle_key`] and
+//! draws into any ratatui frame, so it can be tested with `TestBackend`. The visible
+//! rows are kept as a flattened tree that expanding and collapsing update in place,
+//! and only the rows on screen get labels and are drawn.
+
+use std::collections::{HashMap, HashSet};
 use std::io::{self};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Arc;
 use anyhow::Result;
-use crossterm::event::{self, Event as CEvent, KeyCode, KeyEvent};
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event as CEvent, KeyCode, KeyEvent, KeyEventKind};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use crossterm::{execute, terminal::{EnterAlternateScreen, LeaveAlternateScreen}};
-use ratatui::{Terminal, backend::CrosstermBackend, widgets::{Block, Borders, List, ListItem, Paragraph, ListState}, layout::{Constraint, Direction, Layout}, style::{Style, Color, Modifier}};
+use ratatui::{Terminal, backend::CrosstermBackend, widgets::{Block, Borders, List, ListItem, Paragraph}, layout::{Constraint, Direction, Layout}, style::{Style, Color, Modifier}};
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
 use quick_xml::events::Event;
 use std::io::Cursor;
-use crate::diff::{DiffNode, NodeStatus};
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Row, Table};
+use ratatui::Frame;
+use regex::{Regex, RegexBuilder};
+use similar::{capture_diff_slices, Algorithm, ChangeTag, DiffOp, TextDiff};
+use crate::diff::{ChangeKind, DiffNode, NodeStatus, Summary};
+use crate::render::side_labels;
+use crate::merge::{merge, MergeError, Take};
+use crate::parse::Document;
+use crate::review::{review_key, reviewables, Review};
+use crate::diff::DiffOptions;
 
+/// A shown node. Labels are made only for the rows on screen, see [`row_labels`].
 #[derive(Clone)]
 struct VisibleRow {
     path: Vec<usize>,
     indent: usize,
-    label: String,
     status: NodeStatus,
     has_children: bool,
 }
 
-fn build_rows(root: &DiffNode, expanded: &HashSet<String>) -> Vec<VisibleRow> {
-    let mut rows = Vec::new();
-    fn rec(node: &DiffNode, path: &mut Vec<usize>, indent: usize, expanded: &HashSet<String>, rows: &mut Vec<VisibleRow>) {
-        let key = node.key.as_deref().unwrap_or(&node.tag).to_string();
-        let path_str = path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".");
-        let has_children = !node.children.is_empty();
-        rows.push(VisibleRow { path: path.clone(), indent, label: key.clone(), status: node.status, has_children });
+/// Which parts of the tree are shown.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Filter {
+    #[default]
+    All,
+    /// Hide unchanged subtrees
+    Changes,
+    /// Show only changes of one kind and their ancestors
+    Only(ChangeKind),
+}
+
+impl Filter {
+    fn next(self) -> Self {
+        match self {
+            Filter::All => Filter::Changes,
+            Filter::Changes => Filter::Only(ChangeKind::Added),
+            Filter::Only(ChangeKind::Added) => Filter::Only(ChangeKind::Removed),
+            Filter::Only(ChangeKind::Removed) => Filter::Only(ChangeKind::Changed),
+            Filter::Only(ChangeKind::Changed) => Filter::All,
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Filter::All => "all".to_string(),
+            Filter::Changes => "changes".to_string(),
+            Filter::Only(kind) => format!("{} only", kind),
+        }
+    }
+
+    /// Whether a change of `kind` is shown.
+    fn shows(self, kind: ChangeKind) -> bool {
+        match self {
+            Filter::All | Filter::Changes => true,
+            Filter::Only(k) => k == kind,
+        }
+    }
+
+    /// Whether `node` (whose parent has `parent` status) is shown.
+    fn keeps(self, node: &DiffNode, parent: Option<NodeStatus>) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Changes => node.status != NodeStatus::Unchanged,
+            Filter::Only(kind) => {
+                node.change_kind(parent) == Some(kind)
+                    || (node.status == NodeStatus::Changed && node.children.iter().any(|c| self.keeps(c, Some(node.status))))
+            }
+        }
+    }
+}
+
+/// Tree paths of all changes the filter shows, in document order.
+fn change_paths(root: &DiffNode, filter: Filter) -> Vec<Vec<usize>> {
+    fn rec(node: &DiffNode, parent: Option<NodeStatus>, path: &mut Vec<usize>, filter: Filter, out: &mut Vec<Vec<usize>>) {
+        if node.change_kind(parent).is_some_and(|k| filter.shows(k)) {
+            out.push(path.clone());
+        }
+        if node.status == NodeStatus::Unchanged {
+            return;
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            rec(child, Some(node.status), path, filter, out);
+            path.pop();
+        }
+    }
+    let mut out = Vec::new();
+    rec(root, None, &mut vec![0], filter, &mut out);
+    out
+}
+
+/// The node's name, and the element on each side with its changed values (empty
+/// where a side has none), with its severity.
+fn row_labels(node: &DiffNode) -> (String, String, String) {
+    let mut label = node.key.as_deref().unwrap_or(&node.tag).to_string();
+    if let Some(new_name) = node.renamed_to() {
+        label = format!("{} -> {}", label, new_name);
+    }
+    let (mut left, mut right) = side_labels(node);
+    if let Some(severity) = node.severity {
+        label = format!("{} [{}]", label, severity);
+        let side = if node.status == NodeStatus::Removed { &mut left } else { &mut right };
+        side.push_str(&format!(" [{}]", severity));
+    }
+    (label, left, right)
+}
+
+/// The tree as shown: a flattened list of rows that is kept, and updated in place as
+/// nodes are expanded and collapsed. Rows are in document order, which is also the
+/// order of their paths.
+struct Tree<'a> {
+    root: &'a DiffNode,
+    rows: Vec<VisibleRow>,
+    expanded: HashSet<Vec<usize>>,
+    filter: Filter,
+}
+
+impl<'a> Tree<'a> {
+    /// The tree with only the root expanded.
+    fn new(root: &'a DiffNode) -> Self {
+        let mut tree = Tree { root, rows: Vec::new(), expanded: HashSet::new(), filter: Filter::default() };
+        tree.expanded.insert(vec![0]);
+        tree.rebuild(&[]);
+        tree
+    }
+
+    fn node(&self, row: usize) -> &'a DiffNode {
+        node_by_path(self.root, &self.rows[row].path)
+    }
+
+    /// The row showing the node at `path`, if it is shown.
+    fn row_of(&self, path: &[usize]) -> Option<usize> {
+        self.rows.binary_search_by(|r| r.path.as_slice().cmp(path)).ok()
+    }
+
+    fn is_expanded(&self, row: usize) -> bool {
+        self.expanded.contains(&self.rows[row].path)
+    }
+
+    /// Append rows for the shown children of the node at `path` and, recursively, for
+    /// those of its expanded descendants.
+    fn push_children(&self, path: &mut Vec<usize>, indent: usize, filter: Filter, out: &mut Vec<VisibleRow>) {
+        let node = node_by_path(self.root, path);
+        // the whole of an added or removed subtree is shown
+        let filter = if matches!(node.status, NodeStatus::Added | NodeStatus::Removed) { Filter::All } else { filter };
+        for (i, child) in node.children.iter().enumerate() {
+            if !filter.keeps(child, Some(node.status)) {
+                continue;
+            }
+            path.push(i);
+            out.push(VisibleRow { path: path.clone(), indent, status: child.status, has_children: !child.children.is_empty() });
+            if !child.children.is_empty() && self.expanded.contains(path) {
+                self.push_children(path, indent + 1, filter, out);
+            }
+            path.pop();
+        }
+    }
+
+    /// Rebuild all rows and return the row of the node at `selected`, or of its nearest
+    /// shown ancestor if it is hidden now.
+    fn rebuild(&mut self, selected: &[usize]) -> usize {
+        let mut rows = vec![VisibleRow { path: vec![0], indent: 0, status: self.root.status, has_children: !self.root.children.is_empty() }];
+        if self.expanded.contains(&[0][..]) {
+            self.push_children(&mut vec![0], 1, self.filter, &mut rows);
+        }
+        self.rows = rows;
+        (1..=selected.len()).rev().find_map(|len| self.row_of(&selected[..len])).unwrap_or(0)
+    }
+
+    /// Expand the node at `row`, inserting the rows below it. Returns whether it was
+    /// collapsed.
+    fn expand(&mut self, row: usize) -> bool {
+        let VisibleRow { path, indent, has_children, .. } = self.rows[row].clone();
+        if !has_children || !self.expanded.insert(path.clone()) {
+            return false;
+        }
+        let mut children = Vec::new();
+        self.push_children(&mut path.clone(), indent + 1, self.filter, &mut children);
+        self.rows.splice(row + 1..row + 1, children);
+        true
+    }
+
+    /// Collapse the node at `row`, removing the rows below it. Returns whether it was
+    /// expanded.
+    fn collapse(&mut self, row: usize) -> bool {
+        if !self.expanded.remove(&self.rows[row].path) {
+            return false;
+        }
+        let indent = self.rows[row].indent;
+        let end = row + 1 + self.rows[row + 1..].iter().take_while(|r| r.indent > indent).count();
+        self.rows.drain(row + 1..end);
+        true
+    }
+
+    /// Expand the ancestors of the node at `path` and return its row, unless the
+    /// filter hides it.
+    fn reveal(&mut self, path: &[usize]) -> Option<usize> {
+        for len in 1..path.len() {
+            let row = self.row_of(&path[..len])?;
+            self.expand(row);
+        }
+        self.row_of(path)
+    }
+
+    fn set_filter(&mut self, filter: Filter, selected: &[usize]) -> usize {
+        self.filter = filter;
+        self.rebuild(selected)
+    }
+}
+
+// helper to find a node by its path (path[0] == 0 is root)
+fn node_by_path<'a>(root: &'a DiffNode, path: &[usize]) -> &'a DiffNode {
+    let mut node = root;
+    for idx in path.iter().skip(1) {
+        node = &node.children[*idx];
+    }
+    node
+}
+
+/// Expand every node with children for which `pred(node, depth)` holds (the root has
+/// depth 0).
+fn expand_where(root: &DiffNode, expanded: &mut HashSet<Vec<usize>>, pred: impl Fn(&DiffNode, usize) -> bool) {
+    fn rec(node: &DiffNode, path: &mut Vec<usize>, expanded: &mut HashSet<Vec<usize>>, pred: &dyn Fn(&DiffNode, usize) -> bool) {
+        if node.children.is_empty() || !pred(node, path.len() - 1) {
+            return;
+        }
+        expanded.insert(path.clone());
+        for (i, child) in node.children.iter().enumerate() {
+            path.push(i);
+            rec(child, path, expanded, pred);
+            path.pop();
+        }
+    }
+    rec(root, &mut vec![0], expanded, &pred);
+}
+
+/// The first of `paths` (in document order) after `from`, or before it going
+/// backwards, wrapping around.
+fn next_path<'a>(paths: &'a [Vec<usize>], from: &[usize], forward: bool, inclusive: bool) -> Option<&'a [usize]> {
+    let found = if forward {
+        paths.iter().find(|m| if inclusive { m.as_slice() >= from } else { m.as_slice() > from }).or(paths.first())
+    } else {
+        paths.iter().rev().find(|m| m.as_slice() < from).or(paths.last())
+    };
+    found.map(Vec::as_slice)
+}
+
+// pretty-print an XML fragment using quick-xml Writer (falls back to input on error)
+fn pretty_print_xml(xml: &str) -> String {
+    let mut reader = Reader::from_str(xml);
+    let mut out: Vec<u8> = Vec::new();
+    let mut writer = Writer::new_with_indent(Cursor::new(&mut out), b' ', 4);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Eof) => break,
+            // the writer indents anew; other text, comments, CDATA and PIs are kept as they are
+            Ok(Event::Text(t)) if t.iter().all(u8::is_ascii_whitespace) => {}
+            Ok(e) => {
+                if writer.write_event(e.into_owned()).is_err() { return xml.to_string(); }
+            }
+            Err(_) => return xml.to_string(),
+        }
+        buf.clear();
+    }
+    match std::str::from_utf8(&out) {
+        Ok(s) => s.to_string(),
+        Err(_) => xml.to_string(),
+    }
+}
+
+/// Runs of a line that are (`true`) or are not part of the words that changed.
+type Segments = Vec<(bool, String)>;
+
+/// A line of the raw XML diff.
+#[derive(Clone)]
+struct RawLine {
+    tag: ChangeTag,
+    segments: Segments,
+}
+
+/// Split a line into words (runs of letters, digits, `-`, `_` and `.`) and single
+/// other characters, so that the changed part of a tag or value can be highlighted.
+fn words(line: &str) -> Vec<&str> {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.');
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        let end = i + c.len_utf8();
+        let continues = is_word(c) && line[i + c.len_utf8()..].chars().next().is_some_and(is_word);
+        if !continues {
+            out.push(&line[start..end]);
+            start = end;
+        }
+    }
+    out
+}
+
+/// The words of two lines, each marked whether it differs from the other line.
+fn word_changes(old: &str, new: &str) -> (Segments, Segments) {
+    let (old_words, new_words) = (words(old), words(new));
+    let mut old_segments = Vec::new();
+    let mut new_segments = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_words, &new_words) {
+        let changed = !matches!(op, DiffOp::Equal { .. });
+        old_segments.extend(old_words[op.old_range()].iter().map(|w| (changed, w.to_string())));
+        new_segments.extend(new_words[op.new_range()].iter().map(|w| (changed, w.to_string())));
+    }
+    (old_segments, new_segments)
+}
+
+/// The pretty-printed XML of both sides as blocks of equal lines (the same on both
+/// sides) and of lines that differ. Differing lines are paired up in order to
+/// highlight the words that changed.
+fn raw_blocks(left: &str, right: &str) -> Vec<(Vec<RawLine>, Vec<RawLine>)> {
+    let diff = TextDiff::from_lines(left, right);
+    let mut blocks = Vec::new();
+    for op in diff.ops() {
+        let lines = |tag: ChangeTag| diff.iter_changes(op)
+            .filter(move |c| c.tag() == tag)
+            .map(|c| c.value().trim_end_matches('\n').to_string())
+            .collect::<Vec<_>>();
+        if op.tag() == similar::DiffTag::Equal {
+            let equal: Vec<RawLine> = lines(ChangeTag::Equal).into_iter()
+                .map(|l| RawLine { tag: ChangeTag::Equal, segments: vec![(false, l)] })
+                .collect();
+            blocks.push((equal.clone(), equal));
+            continue;
+        }
+        let (old, new) = (lines(ChangeTag::Delete), lines(ChangeTag::Insert));
+        let mut old_lines = Vec::new();
+        let mut new_lines = Vec::new();
+        for i in 0..old.len().max(new.len()) {
+            let (old_segments, new_segments) = match (old.get(i), new.get(i)) {
+                (Some(o), Some(n)) => word_changes(o, n),
+                (o, n) => (o.iter().map(|o| (false, o.to_string())).collect(), n.iter().map(|n| (false, n.to_string())).collect()),
+            };
+            if i < old.len() {
+                old_lines.push(RawLine { tag: ChangeTag::Delete, segments: old_segments });
+            }
+            if i < new.len() {
+                new_lines.push(RawLine { tag: ChangeTag::Insert, segments: new_segments });
+            }
+        }
+        blocks.push((old_lines, new_lines));
+    }
+    blocks
+}
+
+/// Foreground colour of each character of an XML line: tag names, attribute names,
+/// attribute values and comments.
+fn syntax_colors(line: &str) -> Vec<Option<Color>> {
+    #[derive(PartialEq)]
+    enum State { Text, Name, Attribute, Value(char), Comment }
+    let mut state = State::Text;
+    let mut colors = Vec::new();
+    let chars: Vec<char> = line.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        let color = match state {
+            State::Text if c == '<' => {
+                state = if chars[i..].starts_with(&['<', '!', '-', '-']) { State::Comment } else { State::Name };
+                if state == State::Comment { Some(Color::DarkGray) } else { Some(Color::LightBlue) }
+            }
+            State::Text => None,
+            State::Comment => {
+                if c == '>' && i >= 2 && chars[i - 2..i] == ['-', '-'] {
+                    state = State::Text;
+                }
+                Some(Color::DarkGray)
+            }
+            State::Value(quote) => {
+                if c == quote {
+                    state = State::Attribute;
+                }
+                Some(Color::LightMagenta)
+            }
+            State::Name | State::Attribute if c == '>' => {
+                state = State::Text;
+                Some(Color::LightBlue)
+            }
+            State::Name if c.is_whitespace() => {
+                state = State::Attribute;
+                None
+            }
+            State::Name => Some(Color::LightBlue),
+            State::Attribute if c == '"' || c == '\'' => {
+                state = State::Value(c);
+                Some(Color::LightMagenta)
+            }
+            State::Attribute if c == '/' || c == '?' => Some(Color::LightBlue),
+            State::Attribute if c == '=' || c.is_whitespace() => None,
+            State::Attribute => Some(Color::Cyan),
+        };
+        colors.push(color);
+    }
+    colors
+}
+
+/// A raw diff line with a red or green background, stronger on the changed words,
+/// and XML syntax colours. `None` is a filler for a line the other side does not have.
+fn raw_line(line: Option<&RawLine>, marker: bool) -> Line<'static> {
+    let Some(line) = line else { return Line::from("") };
+    let (sign, background, emphasis) = match line.tag {
+        ChangeTag::Equal => (" ", None, None),
+        ChangeTag::Delete => ("-", Some(Color::Indexed(52)), Some(Color::Indexed(124))),
+        ChangeTag::Insert => ("+", Some(Color::Indexed(22)), Some(Color::Indexed(28))),
+    };
+    let text: String = line.segments.iter().map(|(_, s)| s.as_str()).collect();
+    let colors = syntax_colors(&text);
+    let emphasized = line.segments.iter().flat_map(|(e, s)| std::iter::repeat_n(*e, s.chars().count()));
 
-        if has_children && expanded.contains(&path_str) {
+    let style_of = |color: Option<Color>, emphasized: bool| {
+        let mut style = Style::default();
+        if let Some(color) = color { style = style.fg(color); }
+        if let Some(bg) = if emphasized { emphasis } else { background } { style = style.bg(bg); }
+        style
+    };
+    let mut spans = Vec::new();
+    if marker {
+        spans.push(Span::styled(sign, style_of(None, false)));
+    }
+    let mut run = String::new();
+    let mut run_style = None;
+    for ((c, color), emphasized) in text.chars().zip(colors).zip(emphasized) {
+        let style = style_of(color, emphasized);
+        if run_style.is_some_and(|s| s != style) {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style.unwrap_or_default()));
+        }
+        run_style = Some(style);
+        run.push(c);
+    }
+    if let Some(style) = run_style {
+        spans.push(Span::styled(run, style));
+    }
+    Line::from(spans)
+}
+
+/// The raw XML diff of one node as styled lines: one column in the unified view,
+/// the left and right columns side by side.
+struct RawPane {
+    path: Vec<usize>,
+    columns: Vec<Vec<Line<'static>>>,
+}
+
+impl RawPane {
+    fn new(path: Vec<usize>, blocks: &[(Vec<RawLine>, Vec<RawLine>)], view: ViewMode) -> Self {
+        let columns = match view {
+            ViewMode::Unified => vec![blocks.iter()
+                .flat_map(|(old, new)| match old.first().map(|l| l.tag) {
+                    Some(ChangeTag::Equal) => old.iter().collect::<Vec<_>>(),
+                    _ => old.iter().chain(new).collect(),
+                })
+                .map(|l| raw_line(Some(l), true))
+                .collect()],
+            ViewMode::SideBySide => {
+                let (mut left, mut right) = (Vec::new(), Vec::new());
+                for (old, new) in blocks {
+                    for i in 0..old.len().max(new.len()) {
+                        left.push(raw_line(old.get(i), false));
+                        right.push(raw_line(new.get(i), false));
+                    }
+                }
+                vec![left, right]
+            }
+        };
+        RawPane { path, columns }
+    }
+}
+
+/// Path, tag, key, UUIDs, child counts and changed values of the selected node, all
+/// taken from the diff.
+fn draw_details(f: &mut Frame, area: Rect, node: &DiffNode) {
+    let block = Block::default().borders(Borders::ALL).title("Details");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let parts = Layout::default().direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref()).split(inner);
+
+    let value = |v: Option<&str>| v.map_or_else(|| "<none>".to_string(), |v| v.to_string());
+    let changed = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+
+    let path = if node.path.is_empty() { "-" } else { node.path.as_str() };
+    let key = node.key.as_deref().map_or_else(String::new, |k| format!("    Key: {}", k));
+    let (left_uuid, right_uuid) = (node.left_uuid.as_deref(), node.right_uuid.as_deref());
+    let uuid_line = match node.status {
+        NodeStatus::Added => Line::from(format!("UUID: {}", value(right_uuid))),
+        NodeStatus::Removed => Line::from(format!("UUID: {}", value(left_uuid))),
+        _ if left_uuid != right_uuid => Line::from(Span::styled(format!("UUID: {} -> {}", value(left_uuid), value(right_uuid)), changed)),
+        _ => Line::from(format!("UUID: {}", value(left_uuid))),
+    };
+    let mut counts = [0usize; 4];
+    for c in &node.children {
+        counts[match c.status {
+            NodeStatus::Added => 0,
+            NodeStatus::Removed => 1,
+            NodeStatus::Changed => 2,
+            NodeStatus::Unchanged => 3,
+        }] += 1;
+    }
+    let info = vec![
+        Line::from(format!("Path: {}    Tag: {}{}", path, node.tag, key)),
+        uuid_line,
+        Line::from(format!("Children: {} added, {} removed, {} changed, {} unchanged", counts[0], counts[1], counts[2], counts[3])),
+    ];
+    f.render_widget(Paragraph::new(info), parts[0]);
+
+    // the attributes (@NAME), text and other values that differ between the sides
+    let table_rows: Vec<Row> = node.changes.iter()
+        .map(|c| Row::new(vec![c.what.clone(), value(c.left.as_deref()), value(c.right.as_deref())]).style(changed))
+        .collect();
+    let widths = [Constraint::Percentage(30), Constraint::Percentage(35), Constraint::Percentage(35)];
+    let table = Table::new(table_rows)
+        .header(Row::new(vec!["Changed value", "Left", "Right"]).style(Style::default().add_modifier(Modifier::UNDERLINED)))
+        .widths(&widths);
+    f.render_widget(table, parts[1]);
+}
+
+/// `/` search over the whole tree, not only the visible rows.
+#[derive(Default)]
+struct Search {
+    query: String,
+    /// Also match text and attribute values
+    values: bool,
+    /// Whether the query is being typed
+    editing: bool,
+    /// Tree paths of the matching nodes, in document order (which is also their sort order)
+    matches: Vec<Vec<usize>>,
+}
+
+impl Search {
+    /// The query as a case-insensitive regex, or literally if it is not a valid one.
+    fn pattern(&self) -> Option<Regex> {
+        if self.query.is_empty() {
+            return None;
+        }
+        let build = |p: &str| RegexBuilder::new(p).case_insensitive(true).build();
+        build(&self.query).or_else(|_| build(&regex::escape(&self.query))).ok()
+    }
+
+    fn update(&mut self, root: &DiffNode) {
+        fn rec(node: &DiffNode, path: &mut Vec<usize>, re: &Regex, values: bool, out: &mut Vec<Vec<usize>>) {
+            if node_matches(node, re, values) {
+                out.push(path.clone());
+            }
             for (i, child) in node.children.iter().enumerate() {
                 path.push(i);
-                rec(child, path, indent + 1, expanded, rows);
+                rec(child, path, re, values, out);
                 path.pop();
             }
         }
+        self.matches.clear();
+        if let Some(re) = self.pattern() {
+            rec(root, &mut vec![0], &re, self.values, &mut self.matches);
+        }
+    }
+
+    fn is_match(&self, path: &[usize]) -> bool {
+        self.matches.binary_search_by(|m| m.as_slice().cmp(path)).is_ok()
     }
 
-    let mut p = vec![0usize];
-    rec(root, &mut p, 0, expanded, &mut rows);
-    rows
+    /// Position of the match at `path` and the number of matches, for the status line.
+    fn position(&self, path: &[usize]) -> String {
+        match self.matches.binary_search_by(|m| m.as_slice().cmp(path)) {
+            Ok(i) => format!("{}/{}", i + 1, self.matches.len()),
+            Err(_) => format!("{} matches", self.matches.len()),
+        }
+    }
 }
 
-fn path_to_string(path: &[usize]) -> String {
-    path.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".")
+/// Whether the SHORT-NAME, tag or AUTOSAR path of `node` (and with `values`, its own
+/// text or attribute values) match. Only elements with a SHORT-NAME match by path:
+/// the others share the path of the element they are in.
+fn node_matches(node: &DiffNode, re: &Regex, values: bool) -> bool {
+    if node.key.as_deref().is_some_and(|k| re.is_match(k)) || re.is_match(&node.tag)
+        || (node.is_identifiable() && re.is_match(&node.path)) {
+        return true;
+    }
+    if !values {
+        return false;
+    }
+    let changed = node.changes.iter().flat_map(|c| [&c.left, &c.right]).flatten();
+    // the XML of an element with children holds theirs too; keep to its start tag
+    let own = [&node.left_xml, &node.right_xml].into_iter().flatten()
+        .map(|xml| if node.children.is_empty() { xml.as_str() } else { xml.split_inclusive('>').next().unwrap_or("") });
+    changed.map(String::as_str).chain(own).any(|v| re.is_match(v))
 }
 
-pub fn run_tui(root: &DiffNode) -> Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// Of the change `paths` (in document order), the range with the one at `path`, or
+/// with all those below it for a whole `subtree` or a node that is not a change itself.
+fn changes_at(paths: &[Vec<usize>], path: &[usize], subtree: bool) -> std::ops::Range<usize> {
+    let start = paths.partition_point(|p| p.as_slice() < path);
+    let below = paths[start..].iter().take_while(|p| p.starts_with(path)).count();
+    let exact = paths.get(start).is_some_and(|p| p == path);
+    start..start + if exact && !subtree { 1 } else { below }
+}
 
-    let mut expanded: HashSet<String> = HashSet::new();
-    // start with root expanded
-    expanded.insert("0".to_string());
+/// Rows of the detail panel, borders included.
+const DETAILS_HEIGHT: u16 = 12;
 
-    let mut rows = build_rows(root, &expanded);
-    let mut idx: usize = 0;
-    let mut state = ListState::default();
-    state.select(Some(idx));
+/// First row to show so that `selected` is within a viewport of `height` rows,
+/// scrolling as little as possible from `offset`.
+fn scroll_offset(offset: usize, selected: usize, height: usize) -> usize {
+    if selected < offset {
+        selected
+    } else if selected >= offset + height {
+        selected + 1 - height.max(1)
+    } else {
+        offset
+    }
+}
 
-    #[derive(PartialEq)]
-    enum ViewMode { Unified, SideBySide }
-    let mut view_mode = ViewMode::Unified;
-    let mut show_raw = false;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ViewMode { Unified, SideBySide }
+
+/// What the event loop should do after a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+}
 
+/// The state of the TUI, independent of the terminal: keys go to [`App::handle_key`]
+/// and [`App::draw`] renders it into any ratatui frame.
+pub struct App<'a> {
+    root: &'a DiffNode,
+    messages: &'a [String],
+    tree: Tree<'a>,
+    changes: Vec<Vec<usize>>,
+    summary: Summary,
+    idx: usize,
+    // first row on screen; only the rows in view are drawn
+    offset: usize,
+    view_mode: ViewMode,
+    show_raw: bool,
     // transient status message with expiry
-    let mut status_msg: Option<(String, Instant)> = None;
-
-    // helper to find a node by its path (path[0] == 0 is root)
-    fn node_by_path<'a>(root: &'a DiffNode, path: &'a [usize]) -> &'a DiffNode {
-        let mut node = root;
-        for idx in path.iter().skip(1) {
-            node = &node.children[*idx];
-        }
-        node
-    }
-
-    // pretty-print an XML fragment using quick-xml Writer (falls back to input on error)
-    fn pretty_print_xml(xml: &str) -> String {
-        let mut reader = Reader::from_str(xml);
-        reader.trim_text(true);
-        let mut out: Vec<u8> = Vec::new();
-        let mut writer = Writer::new_with_indent(Cursor::new(&mut out), b' ', 4);
-        let mut buf = Vec::new();
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    if writer.write_event(Event::Start(e.into_owned())).is_err() { return xml.to_string(); }
-                }
-                Ok(Event::End(e)) => {
-                    if writer.write_event(Event::End(e.into_owned())).is_err() { return xml.to_string(); }
-                }
-                Ok(Event::Text(t)) => {
-                    if writer.write_event(Event::Text(t.into_owned())).is_err() { return xml.to_string(); }
-                }
-                Ok(Event::Empty(e)) => {
-                    if writer.write_event(Event::Empty(e.into_owned())).is_err() { return xml.to_string(); }
+    status_msg: Option<(String, Instant)>,
+    search: Search,
+    // the raw pane scrolls on its own and starts at the top for every node
+    raw_scroll: u16,
+    raw_page: u16,
+    // the raw pane of the selected node in the current view, built when first drawn
+    raw: Option<RawPane>,
+    raw_builds: usize,
+    export_dir: PathBuf,
+    // tree paths of all changes, whatever the filter, in document order
+    all_changes: Vec<Vec<usize>>,
+    review: Option<Review>,
+    // review keys of all_changes
+    review_keys: Vec<String>,
+    // the note being typed for the selected node
+    note_edit: Option<String>,
+    merging: Option<Merging<'a>>,
+}
+
+/// Merge mode: the parsed inputs, the side taken for each change so far and where
+/// the merged document is written.
+struct Merging<'a> {
+    left: &'a Document,
+    right: &'a Document,
+    opts: DiffOptions,
+    output: PathBuf,
+    // by tree path; undecided changes take the right side
+    decisions: HashMap<Vec<usize>, Take>,
+}
+
+impl<'a> App<'a> {
+    /// The TUI for `root`, with `messages` shown below the help line.
+    pub fn new(root: &'a DiffNode, messages: &'a [String]) -> Self {
+        let tree = Tree::new(root);
+        let changes = change_paths(root, tree.filter);
+        App {
+            root,
+            messages,
+            tree,
+            changes,
+            summary: root.summary(),
+            idx: 0,
+            offset: 0,
+            view_mode: ViewMode::Unified,
+            show_raw: false,
+            status_msg: None,
+            search: Search::default(),
+            raw_scroll: 0,
+            raw_page: 10,
+            raw: None,
+            raw_builds: 0,
+            export_dir: PathBuf::from("."),
+            all_changes: change_paths(root, Filter::All),
+            review: None,
+            review_keys: Vec::new(),
+            note_edit: None,
+            merging: None,
+        }
+    }
+
+    /// Turn on review mode, restoring the marks of `review` and saving them to its
+    /// file as they are made.
+    pub fn with_review(mut self, review: Review) -> Self {
+        self.review_keys = reviewables(self.root).into_iter().map(|r| r.key).collect();
+        self.review = Some(review);
+        self
+    }
+
+    /// Marks and notes made so far, in review mode.
+    pub fn review(&self) -> Option<&Review> {
+        self.review.as_ref()
+    }
+
+    /// Turn on merge mode for the documents `left` and `right`, whose diff (made with
+    /// `opts`) is shown; `w` writes the merged document to `output`.
+    pub fn with_merge(mut self, left: &'a Document, right: &'a Document, opts: DiffOptions, output: impl Into<PathBuf>) -> Self {
+        self.merging = Some(Merging { left, right, opts, output: output.into(), decisions: HashMap::new() });
+        self
+    }
+
+    /// The merged document with the sides taken so far, in merge mode.
+    pub fn merged(&self) -> Option<Result<Document, MergeError>> {
+        let m = self.merging.as_ref()?;
+        let take = |indices: &[usize]| {
+            let path: Vec<usize> = [0].iter().chain(indices).copied().collect();
+            m.decisions.get(&path).copied().unwrap_or(Take::Right)
+        };
+        Some(merge(m.left, m.right, self.root, &m.opts, take))
+    }
+
+    /// Write exports (`c`) to `dir` instead of the current directory.
+    pub fn with_export_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.export_dir = dir.into();
+        self
+    }
+
+    /// The node on the selected row.
+    pub fn selected(&self) -> &'a DiffNode {
+        self.tree.node(self.idx)
+    }
+
+    /// The status message shown after the help line, such as where an export went.
+    pub fn status(&self) -> Option<&str> {
+        self.status_msg.as_ref().map(|(msg, _)| msg.as_str())
+    }
+
+    fn set_status(&mut self, msg: String) {
+        self.status_msg = Some((msg, Instant::now()));
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind == KeyEventKind::Release {
+            return Action::Continue;
+        }
+        let current = self.tree.rows.get(self.idx).map(|r| r.path.clone()).unwrap_or_default();
+        let action = if self.search.editing {
+            self.edit_search(key.code, &current);
+            Action::Continue
+        } else if self.note_edit.is_some() {
+            self.edit_note(key.code, &current);
+            Action::Continue
+        } else {
+            self.command(key.code, &current)
+        };
+        if self.tree.rows.get(self.idx).map(|r| &r.path) != Some(&current) {
+            self.raw_scroll = 0;
+            self.raw = None;
+        }
+        action
+    }
+
+    fn edit_search(&mut self, code: KeyCode, current: &[usize]) {
+        let search = &mut self.search;
+        match code {
+            KeyCode::Enter => search.editing = false,
+            KeyCode::Esc => *search = Search::default(),
+            KeyCode::Tab => search.values = !search.values,
+            KeyCode::Backspace => { search.query.pop(); }
+            KeyCode::Char(c) => search.query.push(c),
+            _ => {}
+        }
+        // jump to the first match from the selection as the query is typed
+        search.update(self.root);
+        if let Some(target) = next_path(&self.search.matches, current, true, true) {
+            self.idx = self.tree.reveal(target).unwrap_or(self.idx);
+        }
+    }
+
+    fn edit_note(&mut self, code: KeyCode, current: &[usize]) {
+        let Some(note) = &mut self.note_edit else { return };
+        match code {
+            KeyCode::Enter => {
+                let note = std::mem::take(note);
+                self.note_edit = None;
+                if let Some(review) = &mut self.review {
+                    review.set_note(&review_key(self.root, &current[1..]), &note);
                 }
-                Ok(Event::Eof) => break,
-                Ok(_) => {}
-                Err(_) => return xml.to_string(),
+                self.save_review();
             }
-            buf.clear();
+            KeyCode::Esc => self.note_edit = None,
+            KeyCode::Backspace => { note.pop(); }
+            KeyCode::Char(c) => note.push(c),
+            _ => {}
+        }
+    }
+
+    /// Mark the change at `current` as reviewed, or every change below it for a whole
+    /// `subtree` or a node that is not a change itself. If all of them are reviewed
+    /// already, the marks are taken off instead.
+    fn toggle_reviewed(&mut self, current: &[usize], subtree: bool) {
+        let Some(review) = &mut self.review else { return };
+        let keys = &self.review_keys[changes_at(&self.all_changes, current, subtree)];
+        if keys.is_empty() {
+            self.set_status("No changes to mark here".to_string());
+            return;
         }
-        match std::str::from_utf8(&out) {
-            Ok(s) => s.to_string(),
-            Err(_) => xml.to_string(),
+        let reviewed = !keys.iter().all(|k| review.is_reviewed(k));
+        for key in keys {
+            review.set_reviewed(key, reviewed);
         }
+        let what = if reviewed { "reviewed" } else { "not reviewed" };
+        let msg = format!("Marked {} change{} as {}", keys.len(), if keys.len() == 1 { "" } else { "s" }, what);
+        self.set_status(msg);
+        self.save_review();
     }
 
-    loop {
-        terminal.draw(|f| {
-            let size = f.size();
-            let chunks = if show_raw {
-                Layout::default()
-                    .direction(Direction::Vertical)
-                    .margin(1)
-                    .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
-                    .split(size)
+    /// Take `side` for the change at `current` or the changes below it, as
+    /// [`App::toggle_reviewed`] marks them; if they all take it already, they are
+    /// undecided again.
+    fn toggle_take(&mut self, current: &[usize], subtree: bool, side: Take) {
+        let Some(merging) = &mut self.merging else { return };
+        let paths = &self.all_changes[changes_at(&self.all_changes, current, subtree)];
+        if paths.is_empty() {
+            self.set_status("No changes to decide here".to_string());
+            return;
+        }
+        let undo = paths.iter().all(|p| merging.decisions.get(p) == Some(&side));
+        for path in paths {
+            if undo {
+                merging.decisions.remove(path);
             } else {
-                Layout::default()
-                    .direction(Direction::Vertical)
-                    .margin(1)
-                    .constraints([Constraint::Min(1), Constraint::Length(6)].as_ref())
-                    .split(size)
-            };
+                merging.decisions.insert(path.clone(), side);
+            }
+        }
+        let n = paths.len();
+        let plural = if n == 1 { "" } else { "s" };
+        let msg = if undo { format!("{} change{} undecided", n, plural) } else { format!("Taking the {} side of {} change{}", side, n, plural) };
+        self.set_status(msg);
+    }
+
+    /// Write the merged document.
+    fn write_merge(&mut self) {
+        let (Some(merged), Some(merging)) = (self.merged(), &self.merging) else { return };
+        let output = merging.output.clone();
+        let msg = match merged {
+            Ok(doc) => match std::fs::write(&output, doc.to_bytes()) {
+                Ok(()) => format!("Merged ARXML written to {}", output.display()),
+                Err(e) => format!("Failed to write {}: {}", output.display(), e),
+            },
+            Err(e) => format!("Cannot merge: {}", e),
+        };
+        self.set_status(msg);
+    }
 
-            // Top area: either unified full-width or side-by-side two columns
-            match view_mode {
-                ViewMode::Unified => {
-                    let items: Vec<ListItem> = rows.iter().enumerate().map(|(i, r)| {
-                        let mut txt = format!("{}{}", "  ".repeat(r.indent), r.label);
-                        if r.has_children {
-                            let marker = if expanded.contains(&path_to_string(&r.path)) { "▾ " } else { "▸ " };
-                            txt = format!("{}{}", "  ".repeat(r.indent), marker) + &r.label;
-                        }
-                        let style = match r.status {
-                            NodeStatus::Added => Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
-                            NodeStatus::Removed => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-                            NodeStatus::Changed => Style::default().fg(Color::Yellow),
-                            NodeStatus::Unchanged => Style::default(),
-                        };
-                        let mut li = ListItem::new(txt).style(style);
-                        if i == idx { li = li.style(style.patch(Style::default().bg(Color::Blue))); }
-                        li
-                    }).collect();
-                    let list = List::new(items).block(Block::default().borders(Borders::ALL).title("ARXML Diff (Unified)"));
-                    f.render_stateful_widget(list, chunks[0], &mut state);
+    fn save_review(&mut self) {
+        if let Some(Err(e)) = self.review.as_ref().map(Review::save) {
+            self.set_status(format!("Failed to save the review: {}", e));
+        }
+    }
+
+    /// Write the review report next to the exports.
+    fn export_review(&mut self) {
+        let Some(review) = &self.review else { return };
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let fname = self.export_dir.join(format!("arxml-review-{}.txt", ts));
+        let mut out = Vec::new();
+        let written = review.write_report(self.root, &mut out).and_then(|_| std::fs::write(&fname, out));
+        if written.is_ok() {
+            self.set_status(format!("Review report written to {}", fname.display()));
+        } else {
+            self.set_status(format!("Failed to write {}", fname.display()));
+        }
+    }
+
+    fn command(&mut self, code: KeyCode, current: &[usize]) -> Action {
+        let (tree, idx) = (&mut self.tree, &mut self.idx);
+        match code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Down | KeyCode::Char('j') if *idx + 1 < tree.rows.len() => { *idx += 1; }
+            KeyCode::Up | KeyCode::Char('k') if *idx > 0 => { *idx -= 1; }
+            KeyCode::Char('/') => { self.search = Search { editing: true, values: self.search.values, ..Search::default() }; }
+            KeyCode::Char(c @ ('n' | 'N')) => {
+                if let Some(target) = next_path(&self.search.matches, current, c == 'n', false) {
+                    *idx = tree.reveal(target).unwrap_or(*idx);
+                }
+            }
+            KeyCode::Char(c @ (']' | '[')) => {
+                if let Some(target) = next_path(&self.changes, current, c == ']', false) {
+                    *idx = tree.reveal(target).unwrap_or(*idx);
                 }
-                ViewMode::SideBySide => {
-                    let top = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref()).split(chunks[0]);
-                    let mut left_items: Vec<ListItem> = Vec::new();
-                    let mut right_items: Vec<ListItem> = Vec::new();
-                    for (i, r) in rows.iter().enumerate() {
-                        let indent = "  ".repeat(r.indent);
-                        let (ltext, rtext, lstyle, rstyle) = match r.status {
-                            NodeStatus::Added => ("".to_string(), format!("{}{}", indent, r.label), Style::default(), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                            NodeStatus::Removed => (format!("{}{}", indent, r.label), "".to_string(), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD), Style::default()),
-                            NodeStatus::Changed => (format!("{}{}", indent, r.label), format!("{}{}", indent, r.label), Style::default().fg(Color::Yellow), Style::default().fg(Color::Yellow)),
-                            NodeStatus::Unchanged => (format!("{}{}", indent, r.label), format!("{}{}", indent, r.label), Style::default(), Style::default()),
-                        };
-                        let mut li_l = ListItem::new(ltext).style(lstyle);
-                        let mut li_r = ListItem::new(rtext).style(rstyle);
-                        if i == idx {
-                            li_l = li_l.style(lstyle.patch(Style::default().bg(Color::Blue)));
-                            li_r = li_r.style(rstyle.patch(Style::default().bg(Color::Blue)));
-                        }
-                        left_items.push(li_l);
-                        right_items.push(li_r);
+            }
+            KeyCode::Char('f') => {
+                *idx = tree.set_filter(tree.filter.next(), current);
+                self.changes = change_paths(self.root, tree.filter);
+            }
+            KeyCode::Char(c @ ('E' | 'C' | '1'..='9')) => {
+                match c {
+                    'E' => expand_where(self.root, &mut tree.expanded, |n, _| n.status != NodeStatus::Unchanged),
+                    'C' => tree.expanded.clear(),
+                    digit => {
+                        let level = digit.to_digit(10).unwrap_or(1) as usize;
+                        tree.expanded.clear();
+                        expand_where(self.root, &mut tree.expanded, |_, depth| depth < level);
                     }
-                    let left_list = List::new(left_items).block(Block::default().borders(Borders::ALL).title("Left"));
-                    let right_list = List::new(right_items).block(Block::default().borders(Borders::ALL).title("Right"));
-                    f.render_stateful_widget(left_list, top[0], &mut state);
-                    f.render_stateful_widget(right_list, top[1], &mut state);
                 }
+                // the root stays open
+                tree.expanded.insert(vec![0]);
+                *idx = tree.rebuild(current);
             }
-
-            // Bottom area: either raw XML view or help/status text
-            if show_raw {
-                if let Some(sel) = rows.get(idx) {
-                    let node = node_by_path(root, &sel.path);
-                    let mut raw = String::new();
-                    raw.push_str("-- Left --\n");
-                    if let Some(l) = &node.left_xml { raw.push_str(&pretty_print_xml(l)); } else { raw.push_str("<none>"); }
-                    raw.push_str("\n\n-- Right --\n");
-                    if let Some(r) = &node.right_xml { raw.push_str(&pretty_print_xml(r)); } else { raw.push_str("<none>"); }
-                    let para = Paragraph::new(raw).block(Block::default().borders(Borders::ALL).title("Raw XML (r toggles, c: export)"))
-                        .wrap(ratatui::widgets::Wrap { trim: true });
-                    f.render_widget(para, chunks[1]);
+            KeyCode::Left | KeyCode::Char('h') => {
+                // collapse an open node, otherwise go to its parent
+                let collapsed = tree.collapse(*idx);
+                if !collapsed && current.len() > 1 {
+                    *idx = tree.row_of(&current[..current.len() - 1]).unwrap_or(*idx);
                 }
-            } else {
-                // help + (optional) status message
-                let mut help_text = String::from("j/k: move  Enter: expand/collapse  v: toggle view  r: toggle raw  c: export  q: quit");
-                if let Some((msg, when)) = &status_msg {
-                    // expire after 3 seconds
-                    if when.elapsed().as_secs() < 3 {
-                        help_text.push_str("    ");
-                        help_text.push_str(msg);
-                    }
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                // open a closed node, otherwise go to its first child
+                let expanded = tree.expand(*idx);
+                if !expanded && tree.rows.get(*idx + 1).is_some_and(|next| next.path.len() > current.len()) {
+                    *idx += 1;
+                }
+            }
+            KeyCode::Enter => {
+                // toggle expand on selected row if it has children
+                let collapsed = tree.collapse(*idx);
+                if !collapsed {
+                    tree.expand(*idx);
                 }
-                let help = Paragraph::new(help_text);
-                f.render_widget(help, chunks[1]);
             }
-        })?;
+            KeyCode::Char('v') => {
+                self.view_mode = if self.view_mode == ViewMode::Unified { ViewMode::SideBySide } else { ViewMode::Unified };
+                self.raw = None;
+            }
+            KeyCode::Char('r') => {
+                self.show_raw = !self.show_raw;
+                self.raw = None;
+            }
+            KeyCode::PageDown if self.show_raw => { self.raw_scroll = self.raw_scroll.saturating_add(self.raw_page); }
+            KeyCode::PageUp if self.show_raw => { self.raw_scroll = self.raw_scroll.saturating_sub(self.raw_page); }
+            KeyCode::Char('c') => self.export(),
+            KeyCode::Char(c @ ('m' | 'M')) if self.review.is_some() => self.toggle_reviewed(current, c == 'M'),
+            KeyCode::Char('a') if self.review.is_some() => {
+                let key = review_key(self.root, &current[1..]);
+                self.note_edit = Some(self.review.as_ref().and_then(|r| r.note(&key)).unwrap_or("").to_string());
+            }
+            KeyCode::Char('u') if self.review.is_some() => {
+                let review = self.review.as_ref();
+                let unreviewed: Vec<Vec<usize>> = self.all_changes.iter().zip(&self.review_keys)
+                    .filter(|(_, key)| review.is_some_and(|r| !r.is_reviewed(key)))
+                    .map(|(p, _)| p.clone())
+                    .collect();
+                if let Some(target) = next_path(&unreviewed, current, true, false) {
+                    *idx = tree.reveal(target).unwrap_or(*idx);
+                }
+            }
+            KeyCode::Char('R') if self.review.is_some() => self.export_review(),
+            KeyCode::Char(c @ ('<' | '>' | '{' | '}')) if self.merging.is_some() => {
+                let side = if matches!(c, '<' | '{') { Take::Left } else { Take::Right };
+                self.toggle_take(current, matches!(c, '{' | '}'), side);
+            }
+            KeyCode::Char('w') if self.merging.is_some() => self.write_merge(),
+            _ => {}
+        }
+        Action::Continue
+    }
 
-        // handle input
-        if event::poll(std::time::Duration::from_millis(200))? {
-            if let CEvent::Key(KeyEvent { code, .. }) = event::read()? {
-                match code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Down | KeyCode::Char('j') => { if idx + 1 < rows.len() { idx += 1; state.select(Some(idx)); } }
-                    KeyCode::Up | KeyCode::Char('k') => { if idx > 0 { idx -= 1; state.select(Some(idx)); } }
-                    KeyCode::Char('v') => { view_mode = if view_mode == ViewMode::Unified { ViewMode::SideBySide } else { ViewMode::Unified }; }
-                    KeyCode::Char('r') => { show_raw = !show_raw; }
-                    KeyCode::Char('c') => {
-                        // Export selected node (pretty-printed) to a file
-                        if let Some(sel) = rows.get(idx) {
-                            let node = node_by_path(root, &sel.path);
-                            let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-                            let fname = format!("arxml-{}-{}.xml", node.tag, ts);
-                            let left = node.left_xml.as_deref().map(pretty_print_xml);
-                            let right = node.right_xml.as_deref().map(pretty_print_xml);
-                            let mut out = String::new();
-                            out.push_str("<!-- Exported by arxml-diff -->\n");
-                            out.push_str("<!-- Left -->\n");
-                            out.push_str(left.as_deref().unwrap_or("<none>"));
-                            out.push_str("\n\n<!-- Right -->\n");
-                            out.push_str(right.as_deref().unwrap_or("<none>"));
-                            if std::fs::write(&fname, out).is_ok() {
-                                status_msg = Some((format!("Exported to {}", fname), Instant::now()));
-                            } else {
-                                status_msg = Some((format!("Failed to write {}", fname), Instant::now()));
-                            }
-                        }
+    /// Export the selected node (pretty-printed) to a file.
+    fn export(&mut self) {
+        let node = self.selected();
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let fname = self.export_dir.join(format!("arxml-{}-{}.xml", node.tag, ts));
+        let left = node.left_xml.as_deref().map(pretty_print_xml);
+        let right = node.right_xml.as_deref().map(pretty_print_xml);
+        let mut out = String::new();
+        out.push_str("<!-- Exported by arxml-diff -->\n");
+        out.push_str("<!-- Left -->\n");
+        out.push_str(left.as_deref().unwrap_or("<none>"));
+        out.push_str("\n\n<!-- Right -->\n");
+        out.push_str(right.as_deref().unwrap_or("<none>"));
+        if std::fs::write(&fname, out).is_ok() {
+            self.set_status(format!("Exported to {}", fname.display()));
+        } else {
+            self.set_status(format!("Failed to write {}", fname.display()));
+        }
+    }
+
+    /// Draw the TUI into `f`. Besides drawing, this only scrolls the tree and the raw
+    /// pane to fit the frame.
+    pub fn draw(&mut self, f: &mut Frame) {
+        let size = f.size();
+        let chunks = if self.show_raw {
+            Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+                .split(size)
+        } else {
+            Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([Constraint::Min(1), Constraint::Length(DETAILS_HEIGHT), Constraint::Length(4)].as_ref())
+                .split(size)
+        };
+
+        self.draw_tree(f, chunks[0]);
+        // Bottom area: either raw XML view or details and help/status text
+        if self.show_raw {
+            self.draw_raw(f, chunks[1]);
+        } else {
+            draw_details(f, chunks[1], self.selected());
+            self.draw_help(f, chunks[2]);
+        }
+    }
+
+    /// The rows in view, either unified full-width or side-by-side in two columns.
+    fn draw_tree(&mut self, f: &mut Frame, area: Rect) {
+        let rows = &self.tree.rows;
+        let (idx, tree, search) = (self.idx, &self.tree, &self.search);
+        let height = area.height.saturating_sub(2) as usize;
+        self.offset = scroll_offset(self.offset, idx, height);
+        let in_view = self.offset..(self.offset + height).min(rows.len());
+
+        let counter = {
+            let at = rows.get(idx).and_then(|r| self.changes.binary_search(&r.path).ok());
+            let at = at.map_or_else(String::new, |i| format!("change {}/{}  ", i + 1, self.changes.len()));
+            let summary = &self.summary;
+            let reviewed = self.review.as_ref().map_or_else(String::new, |review| {
+                let done = self.review_keys.iter().filter(|key| review.is_reviewed(key)).count();
+                format!("  {}/{} reviewed", done, self.review_keys.len())
+            });
+            let decided = self.merging.as_ref().map_or_else(String::new, |m| {
+                let left = m.decisions.values().filter(|&&t| t == Take::Left).count();
+                let right = m.decisions.len() - left;
+                format!("  merge: {} left, {} right, {} undecided", left, right, self.all_changes.len() - m.decisions.len())
+            });
+            format!(" {}+{} -{} ~{} ↔{}  filter: {}{}{} ", at, summary.counts.added, summary.counts.removed, summary.counts.changed, summary.counts.moved, tree.filter.label(), reviewed, decided)
+        };
+        // review marks after the label: ✓ reviewed, ✎ has a note
+        // and the side taken in a merge: ◀ left, ▶ right
+        let marks = |path: &[usize]| {
+            let mut marks = String::new();
+            if let Some(review) = &self.review {
+                let key = review_key(self.root, &path[1..]);
+                if review.is_reviewed(&key) { marks.push_str(" ✓"); }
+                if review.note(&key).is_some() { marks.push_str(" ✎"); }
+            }
+            match self.merging.as_ref().and_then(|m| m.decisions.get(path)) {
+                Some(Take::Left) => marks.push_str(" ◀"),
+                Some(Take::Right) => marks.push_str(" ▶"),
+                None => {}
+            }
+            marks
+        };
+        match self.view_mode {
+            ViewMode::Unified => {
+                let items: Vec<ListItem> = in_view.map(|i| {
+                    let r = &rows[i];
+                    let (label, _, _) = row_labels(tree.node(i));
+                    let label = label + &marks(&r.path);
+                    let mut txt = format!("{}{}", "  ".repeat(r.indent), label);
+                    if r.has_children {
+                        let marker = if tree.is_expanded(i) { "▾ " } else { "▸ " };
+                        txt = format!("{}{}", "  ".repeat(r.indent), marker) + &label;
                     }
-                    KeyCode::Enter => {
-                        // toggle expand on selected row if it has children
-                        if let Some(sel) = rows.get(idx) {
-                            if sel.has_children {
-                                let path = path_to_string(&sel.path);
-                                if expanded.contains(&path) { expanded.remove(&path); }
-                                else { expanded.insert(path); }
-                                rows = build_rows(root, &expanded);
-                                if idx >= rows.len() { idx = rows.len().saturating_sub(1); }
-                                state.select(Some(idx));
-                            }
-                        }
+                    let style = match r.status {
+                        NodeStatus::Added => Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                        NodeStatus::Removed => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        NodeStatus::Changed => Style::default().fg(Color::Yellow),
+                        NodeStatus::Unchanged => Style::default(),
+                    };
+                    let style = if search.is_match(&r.path) { style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD) } else { style };
+                    let mut li = ListItem::new(txt).style(style);
+                    if i == idx { li = li.style(style.patch(Style::default().bg(Color::Blue))); }
+                    li
+                }).collect();
+                let list = List::new(items).block(Block::default().borders(Borders::ALL).title(format!("ARXML Diff (Unified){}", counter)));
+                f.render_widget(list, area);
+            }
+            ViewMode::SideBySide => {
+                let top = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref()).split(area);
+                let mut left_items: Vec<ListItem> = Vec::new();
+                let mut right_items: Vec<ListItem> = Vec::new();
+                for i in in_view {
+                    let r = &rows[i];
+                    let (_, mut left, mut right) = row_labels(tree.node(i));
+                    let side = if right.is_empty() { &mut left } else { &mut right };
+                    side.push_str(&marks(&r.path));
+                    // a side without the element gets a blank filler row
+                    let indent = "  ".repeat(r.indent);
+                    let cell = |text: &str| if text.is_empty() { String::new() } else { format!("{}{}", indent, text) };
+                    let (ltext, rtext) = (cell(&left), cell(&right));
+                    let (lstyle, rstyle) = match r.status {
+                        NodeStatus::Added => (Style::default(), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                        NodeStatus::Removed => (Style::default().fg(Color::Red).add_modifier(Modifier::BOLD), Style::default()),
+                        NodeStatus::Changed => (Style::default().fg(Color::Yellow), Style::default().fg(Color::Yellow)),
+                        NodeStatus::Unchanged => (Style::default(), Style::default()),
+                    };
+                    let (lstyle, rstyle) = if search.is_match(&r.path) {
+                        (lstyle.add_modifier(Modifier::UNDERLINED | Modifier::BOLD), rstyle.add_modifier(Modifier::UNDERLINED | Modifier::BOLD))
+                    } else {
+                        (lstyle, rstyle)
+                    };
+                    let mut li_l = ListItem::new(ltext).style(lstyle);
+                    let mut li_r = ListItem::new(rtext).style(rstyle);
+                    if i == idx {
+                        li_l = li_l.style(lstyle.patch(Style::default().bg(Color::Blue)));
+                        li_r = li_r.style(rstyle.patch(Style::default().bg(Color::Blue)));
                     }
-                    _ => {}
+                    left_items.push(li_l);
+                    right_items.push(li_r);
                 }
+                // both lists show the same rows, so they scroll together
+                let left_list = List::new(left_items).block(Block::default().borders(Borders::ALL).title("Left"));
+                let right_list = List::new(right_items).block(Block::default().borders(Borders::ALL).title(format!("Right{}", counter)));
+                f.render_widget(left_list, top[0]);
+                f.render_widget(right_list, top[1]);
+            }
+        }
+    }
+
+    /// The raw XML of the selected node as a line diff, laid out like the tree.
+    fn draw_raw(&mut self, f: &mut Frame, area: Rect) {
+        let path = self.tree.rows.get(self.idx).map(|r| r.path.as_slice()).unwrap_or_default();
+        if self.raw.as_ref().is_none_or(|raw| raw.path != path) {
+            let node = self.selected();
+            let pretty = |xml: &Option<String>| xml.as_deref().map(pretty_print_xml).unwrap_or_default();
+            let blocks = raw_blocks(&pretty(&node.left_xml), &pretty(&node.right_xml));
+            self.raw = Some(RawPane::new(path.to_vec(), &blocks, self.view_mode));
+            self.raw_builds += 1;
+        }
+        let Some(raw) = &self.raw else { return };
+
+        let title = "Raw XML (r toggles, PgUp/PgDn: scroll, c: export)";
+        let height = area.height.saturating_sub(2) as usize;
+        self.raw_page = area.height.saturating_sub(3).max(1);
+        self.raw_scroll = self.raw_scroll.min(raw.columns[0].len().saturating_sub(1) as u16);
+        // only the lines in view are handed to the paragraphs
+        let in_view = |lines: &[Line<'static>]| {
+            let from = (self.raw_scroll as usize).min(lines.len());
+            lines[from..(from + height).min(lines.len())].to_vec()
+        };
+        let areas = if raw.columns.len() == 1 {
+            vec![area]
+        } else {
+            Layout::default().direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref()).split(area).to_vec()
+        };
+        for (i, (lines, area)) in raw.columns.iter().zip(areas).enumerate() {
+            let title = if i == 0 { title } else { "Right" };
+            f.render_widget(Paragraph::new(in_view(lines)).block(Block::default().borders(Borders::ALL).title(title)), area);
+        }
+    }
+
+    /// How many times the raw pane was built; drawing the same node again reuses it.
+    pub fn raw_builds(&self) -> usize {
+        self.raw_builds
+    }
+
+    /// The help line, or the search being typed, then the status and messages.
+    fn draw_help(&self, f: &mut Frame, area: Rect) {
+        let search = &self.search;
+        let mut help_text = String::from("j/k: move  Enter/←/→: collapse/expand  E/C/1-9: expand changed/collapse all/to level  ]/[: next/prev change  f: filter  /: search  n/N: next/prev match  v: toggle view  r: toggle raw  c: export  q: quit");
+        if self.review.is_some() {
+            help_text.push_str("  m/M: mark reviewed (subtree)  a: note  u: next unreviewed  R: review report");
+        }
+        if self.merging.is_some() {
+            help_text.push_str("  </>: take left/right  {/}: for the subtree  w: write merge (undecided: right)");
+        }
+        if search.editing || !search.query.is_empty() {
+            let at = self.tree.rows.get(self.idx).map(|r| search.position(&r.path)).unwrap_or_default();
+            help_text = format!(
+                "/{}{}  [{}]  {}",
+                search.query,
+                if search.editing { "_" } else { "" },
+                if search.values { "names, paths and values" } else { "names and paths" },
+                at,
+            );
+            if search.editing {
+                help_text.push_str("    Enter: done  Esc: clear  Tab: toggle values");
             }
         }
+        if let Some(note) = &self.note_edit {
+            help_text = format!("note: {}_    Enter: save  Esc: cancel", note);
+        }
+        if let Some((msg, when)) = &self.status_msg {
+            // expire after 3 seconds
+            if when.elapsed().as_secs() < 3 {
+                help_text.push_str("    ");
+                help_text.push_str(msg);
+            }
+        }
+        if let Some(review) = &self.review {
+            let key = review_key(self.root, &self.tree.rows[self.idx].path[1..]);
+            if let Some(note) = review.note(&key) {
+                help_text.push_str("\nnote: ");
+                help_text.push_str(note);
+            }
+        }
+        for m in self.messages {
+            help_text.push('\n');
+            help_text.push_str(m);
+        }
+        let help = Paragraph::new(help_text).wrap(ratatui::widgets::Wrap { trim: false });
+        f.render_widget(help, area);
     }
+}
+
+/// Puts the terminal into raw mode on the alternate screen, and restores it when
+/// dropped or when the program panics.
+struct TerminalGuard {
+    previous_hook: Arc<PanicHook>,
+}
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-    Ok(())
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        let previous_hook: Arc<PanicHook> = Arc::from(panic::take_hook());
+        let previous = Arc::clone(&previous_hook);
+        panic::set_hook(Box::new(move |info| {
+            // restore first, so the panic message isn't lost on the alternate screen
+            restore_terminal();
+            previous(info);
+        }));
+        // from here on, dropping the guard undoes what was done so far
+        let guard = TerminalGuard { previous_hook };
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+type PanicHook = Box<dyn Fn(&panic::PanicHookInfo) + Sync + Send>;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+        // put the previous hook back; hooks can't be changed while panicking
+        if !std::thread::panicking() {
+            let previous = Arc::clone(&self.previous_hook);
+            panic::set_hook(Box::new(move |info| previous(info)));
+        }
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// Run `app` in the terminal until `q`.
+pub fn run_tui(mut app: App) -> Result<()> {
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    loop {
+        terminal.draw(|f| app.draw(f))?;
+        if event::poll(std::time::Duration::from_millis(200))? {
+            if let CEvent::Key(key) = event::read()? {
+                if app.handle_key(key) == Action::Quit {
+                    return Ok(());
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,150 @@
+use arxml_diff::diff::{compute_tree_diff, compute_tree_diff_with, DiffNode, DiffOptions, KeyRule, MatchMode, Normalization, NodeStatus};
+use arxml_diff::error::Error;
+use arxml_diff::parse::{parse_document, parse_reader, parse_str, parse_str_with, ParseOptions};
+use std::io::{BufReader, Write};
+
+mod common;
+
+use common::autosar_doc;
+
+fn diff(left: &str, right: &str, opts: &DiffOptions) -> DiffNode {
+    let l = parse_str(&autosar_doc(left)).expect("parse left");
+    let r = parse_str(&autosar_doc(right)).expect("parse right");
+    compute_tree_diff_with(&l.root, &r.root, opts)
+}
+
+fn statuses(d: &DiffNode) -> Vec<(Option<&str>, NodeStatus)> {
+    d.children.iter().map(|c| (c.key.as_deref(), c.status)).collect()
+}
+
+#[test]
+fn parse_str_and_reader_match_parse_document() {
+    let from_file = parse_document("tests/fixtures/sample-left.arxml").expect("parse file");
+    let text = std::fs::read_to_string("tests/fixtures/sample-left.arxml").unwrap();
+    assert_eq!(parse_str(&text).expect("parse str").root, from_file.root);
+
+    let reader = BufReader::new(std::fs::File::open("tests/fixtures/sample-left.arxml").unwrap());
+    assert_eq!(parse_reader(reader).expect("parse reader").root, from_file.root);
+
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(text.as_bytes()).unwrap();
+    let compressed = gz.finish().unwrap();
+    assert_eq!(parse_reader(&compressed[..]).expect("parse gzip reader").root, from_file.root);
+}
+
+#[test]
+fn parse_str_ignores_the_declared_encoding() {
+    for encoding in ["ISO-8859-1", "UTF-16"] {
+        let xml = format!("<?xml version=\"1.0\" encoding=\"{}\"?>\n<AUTOSAR><DESC>Grüße</DESC></AUTOSAR>", encoding);
+        let doc = parse_str(&xml).unwrap_or_else(|e| panic!("parse {}: {}", encoding, e));
+        assert_eq!(doc.root.children[0].text().as_deref(), Some("Grüße"), "{}", encoding);
+        assert_eq!(doc.encoding, "UTF-8");
+    }
+}
+
+#[test]
+fn errors_are_typed() {
+    match parse_str("<AUTOSAR><A></B></AUTOSAR>") {
+        Err(Error::Parse(d)) => assert_eq!((d.path.as_str(), d.line), ("<string>", 1)),
+        other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+    }
+    let lenient = parse_str_with("<AUTOSAR><A></B></AUTOSAR>", &ParseOptions { lenient: true }).expect("lenient parse");
+    assert!(!lenient.warnings.is_empty());
+
+    match parse_document("tests/fixtures/does-not-exist.arxml") {
+        Err(Error::Io { path, .. }) => assert_eq!(path, "tests/fixtures/does-not-exist.arxml"),
+        other => panic!("expected an io error, got {:?}", other.map(|_| ())),
+    }
+    match parse_reader(&[0x1f, 0x8b, 0, 0][..]) {
+        Err(e @ Error::Input { .. }) => assert!(e.to_string().contains("gzip")),
+        other => panic!("expected an input error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn unordered_matching_ignores_sibling_order() {
+    let left = "<A><SHORT-NAME>a</SHORT-NAME></A><B><SHORT-NAME>b</SHORT-NAME></B><C><SHORT-NAME>c</SHORT-NAME></C>";
+    let right = "<C><SHORT-NAME>c</SHORT-NAME></C><A><SHORT-NAME>a</SHORT-NAME></A><D><SHORT-NAME>d</SHORT-NAME></D>";
+
+    let ordered = diff(left, right, &DiffOptions::default());
+    assert_eq!(ordered.status, NodeStatus::Changed);
+    // a moved sibling shows up as removed and added again
+    let moved = statuses(&ordered).iter()
+        .filter(|(_, s)| *s == NodeStatus::Removed)
+        .any(|(k, _)| statuses(&ordered).contains(&(*k, NodeStatus::Added)));
+    assert!(moved);
+
+    let unordered = diff(left, right, &DiffOptions::builder().matching(MatchMode::Unordered).build());
+    assert_eq!(statuses(&unordered), vec![
+        (Some("c"), NodeStatus::Unchanged),
+        (Some("a"), NodeStatus::Unchanged),
+        (Some("b"), NodeStatus::Removed),
+        (Some("d"), NodeStatus::Added),
+    ]);
+}
+
+#[test]
+fn ignored_tags_and_attributes_do_not_count() {
+    let left = "<P T=\"2024-01-01\"><SHORT-NAME>p</SHORT-NAME><ADMIN-DATA><X>1</X></ADMIN-DATA></P>";
+    let right = "<P T=\"2025-06-30\"><SHORT-NAME>p</SHORT-NAME><ADMIN-DATA><X>2</X></ADMIN-DATA></P>";
+    assert_eq!(diff(left, right, &DiffOptions::default()).status, NodeStatus::Changed);
+
+    let opts = DiffOptions::builder().ignore_tag("ADMIN-DATA").ignore_attribute("T").build();
+    let d = diff(left, right, &opts);
+    assert_eq!(d.status, NodeStatus::Unchanged);
+    assert!(d.children[0].children.is_empty());
+}
+
+#[test]
+fn key_rules_identify_elements_without_short_name() {
+    let value = |def: &str, v: &str| format!(
+        "<ECUC-NUMERICAL-PARAM-VALUE><DEFINITION-REF>{}</DEFINITION-REF><VALUE>{}</VALUE></ECUC-NUMERICAL-PARAM-VALUE>", def, v
+    );
+    let left = format!("{}{}", value("/Def/A", "1"), value("/Def/B", "2"));
+    let right = format!("{}{}", value("/Def/B", "2"), value("/Def/A", "1"));
+
+    let opts = DiffOptions::builder()
+        .matching(MatchMode::Unordered)
+        .key_rule("ECUC-NUMERICAL-PARAM-VALUE", KeyRule::ChildText("DEFINITION-REF".to_string()))
+        .build();
+    let d = diff(&left, &right, &opts);
+    assert_eq!(d.status, NodeStatus::Unchanged);
+    assert_eq!(statuses(&d), vec![(Some("/Def/B"), NodeStatus::Unchanged), (Some("/Def/A"), NodeStatus::Unchanged)]);
+
+    assert_eq!("@DEST".parse::<KeyRule>(), Ok(KeyRule::Attribute("DEST".to_string())));
+    assert_eq!("short-name".parse::<KeyRule>(), Ok(KeyRule::ShortName));
+}
+
+#[test]
+fn normalization_relaxes_value_comparison() {
+    let left = "<V><SHORT-NAME>v</SHORT-NAME><DESC>Max  speed\n limit</DESC><VALUE>0x10</VALUE><UNIT>KMH</UNIT></V>";
+    let right = "<V><SHORT-NAME>v</SHORT-NAME><DESC>Max speed limit</DESC><VALUE>16.0</VALUE><UNIT>kmh</UNIT></V>";
+    let l = parse_str(&autosar_doc(left)).unwrap();
+    let r = parse_str(&autosar_doc(right)).unwrap();
+    assert_eq!(compute_tree_diff(&l.root, &r.root).status, NodeStatus::Changed);
+
+    let all = Normalization { collapse_whitespace: true, ignore_case: true, numbers: true };
+    let d = compute_tree_diff_with(&l.root, &r.root, &DiffOptions::builder().normalization(all).build());
+    assert_eq!(d.status, NodeStatus::Unchanged);
+
+    let no_numbers = Normalization { numbers: false, ..all };
+    let d = compute_tree_diff_with(&l.root, &r.root, &DiffOptions::builder().normalization(no_numbers).build());
+    let v = &d.children[0];
+    let changed: Vec<_> = v.children.iter().filter(|c| c.status != NodeStatus::Unchanged).map(|c| c.tag.as_str()).collect();
+    assert_eq!(changed, vec!["VALUE"]);
+}
+
+#[test]
+fn large_integers_are_compared_exactly() {
+    let numbers = Normalization { numbers: true, ..Normalization::default() };
+    let opts = DiffOptions::builder().normalization(numbers).build();
+    let value = |v: &str| format!("<V><SHORT-NAME>v</SHORT-NAME><VALUE>{}</VALUE></V>", v);
+    for (left, right, status) in [
+        ("9007199254740993", "9007199254740992", NodeStatus::Changed),
+        ("0xFFFFFFFFFFFFFFFF", "0xFFFFFFFFFFFFFFFE", NodeStatus::Changed),
+        ("0xFFFFFFFFFFFFFFFF", "18446744073709551615", NodeStatus::Unchanged),
+        ("-0x10", "-16.0", NodeStatus::Unchanged),
+    ] {
+        assert_eq!(diff(&value(left), &value(right), &opts).status, status, "{} vs {}", left, right);
+    }
+}
@@ -0,0 +1,50 @@
+use std::process::{Command, Output};
+
+const LEFT: &str = "tests/fixtures/sample-left.arxml";
+const RIGHT: &str = "tests/fixtures/sample-right.arxml";
+
+fn arxml_diff(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_arxml-diff")).args(args).output().expect("run arxml-diff")
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+#[test]
+fn exit_status_follows_diff() {
+    let same = arxml_diff(&[LEFT, LEFT]);
+    assert_eq!(same.status.code(), Some(0));
+    let differ = arxml_diff(&[LEFT, RIGHT]);
+    assert_eq!(differ.status.code(), Some(1));
+    assert!(!stdout(&differ).is_empty());
+
+    let missing = arxml_diff(&[LEFT, "tests/fixtures/does-not-exist.arxml"]);
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("does-not-exist.arxml"));
+    let malformed = arxml_diff(&[LEFT, "tests/fixtures/malformed.arxml"]);
+    assert_eq!(malformed.status.code(), Some(2));
+}
+
+#[test]
+fn quiet_prints_nothing() {
+    for (right, code) in [(LEFT, 0), (RIGHT, 1)] {
+        let out = arxml_diff(&["--quiet", LEFT, right]);
+        assert_eq!(out.status.code(), Some(code));
+        assert!(out.stdout.is_empty() && out.stderr.is_empty());
+    }
+}
+
+#[test]
+fn brief_prints_summary_only() {
+    let out = arxml_diff(&["--brief", LEFT, RIGHT]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        stdout(&out),
+        format!("Files {} and {} differ: 1 added, 1 removed, 0 changed, 0 moved (0 error, 1 warning, 1 info)\n", LEFT, RIGHT),
+    );
+
+    let out = arxml_diff(&["--brief", LEFT, LEFT]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty());
+}
@@ -0,0 +1,59 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use arxml_diff::diff::{compute_tree_diff, DiffNode};
+use arxml_diff::parse::parse_file;
+use arxml_diff::policy::Policy;
+use arxml_diff::render::Renderer;
+use arxml_diff::tui::{Action, App};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+
+pub const LEFT: &str = "tests/fixtures/sample-left.arxml";
+pub const RIGHT: &str = "tests/fixtures/sample-right.arxml";
+
+/// The sample fixtures diffed and rated with the AUTOSAR policy.
+pub fn sample_diff() -> DiffNode {
+    let mut diff = compute_tree_diff(&parse_file(LEFT).expect("parse left"), &parse_file(RIGHT).expect("parse right"));
+    Policy::autosar().apply(&mut diff);
+    diff
+}
+
+/// An AUTOSAR document with `body` directly under the root.
+pub fn autosar_doc(body: &str) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<AUTOSAR xmlns=\"http://autosar.org/schema/r4.0\">{}</AUTOSAR>", body)
+}
+
+/// An AUTOSAR document with `elements` in the ELEMENTS of package `/Pkg`.
+pub fn package_doc(elements: &str) -> String {
+    format!("<AUTOSAR xmlns=\"http://autosar.org/schema/r4.0\"><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>{}</ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>", elements)
+}
+
+pub fn render(renderer: &dyn Renderer, root: &DiffNode) -> String {
+    let mut out = Vec::new();
+    renderer.render(root, &mut out).expect("render");
+    String::from_utf8(out).expect("utf-8 output")
+}
+
+/// Types `keys` into `app` and returns the action of the last one.
+pub fn press(app: &mut App, keys: &str) -> Action {
+    keys.chars().map(|c| app.handle_key(KeyEvent::from(KeyCode::Char(c)))).last().unwrap_or(Action::Continue)
+}
+
+/// The screen after drawing `app` on a `width` x `height` terminal, one string per line.
+pub fn screen_sized(app: &mut App, width: u16, height: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("terminal");
+    terminal.draw(|f| app.draw(f)).expect("draw");
+    let buffer = terminal.backend().buffer();
+    buffer.content.chunks(width as usize).map(|row| row.iter().map(|cell| cell.symbol.as_str()).collect()).collect()
+}
+
+/// The screen after drawing `app`, wide enough for the merge status line.
+pub fn screen(app: &mut App) -> Vec<String> {
+    screen_sized(app, 140, 30)
+}
+
+pub fn shows(app: &mut App, text: &str) -> bool {
+    screen(app).iter().any(|line| line.contains(text))
+}
@@ -0,0 +1,89 @@
+use arxml_diff::parse::{parse_document, parse_file, ContentNode, Element};
+use arxml_diff::diff::{compute_tree_diff, compute_tree_diff_with, DiffNode, DiffOptions, NodeStatus};
+
+fn find<'a>(e: &'a Element, tag: &str) -> &'a Element {
+    e.children.iter().find(|c| c.tag == tag).unwrap_or_else(|| panic!("no {} in {}", tag, e.tag))
+}
+
+fn find_diff<'a>(d: &'a DiffNode, tag: &str) -> &'a DiffNode {
+    d.children.iter().find(|c| c.tag == tag).unwrap_or_else(|| panic!("no {} in {}", tag, d.tag))
+}
+
+#[test]
+fn mixed_content_keeps_text_interleaved_with_children() {
+    let root = parse_file("tests/fixtures/mixed-left.arxml").expect("parse");
+    let pkg = &root.children[0].children[0];
+    let l2 = find(find(pkg, "DESC"), "L-2");
+    assert_eq!(l2.children.len(), 2);
+    let texts: Vec<_> = l2.content.iter().map(|c| (c.pos, c.node.clone())).collect();
+    assert_eq!(texts, vec![
+        (0, ContentNode::Text("Limits the ".to_string())),
+        (1, ContentNode::Text(" speed to ".to_string())),
+        (2, ContentNode::Text(" km/h.".to_string())),
+    ]);
+    assert_eq!(l2.text().as_deref(), Some("Limits the speed to km/h."));
+
+    let xml = l2.serialize();
+    assert_eq!(xml, "<L-2 L=\"EN\">Limits the <E TYPE=\"BOLD\">vehicle</E> speed to <TT>120</TT> km/h.</L-2>");
+}
+
+#[test]
+fn comments_cdata_and_pis_are_kept_and_round_trip() {
+    let doc = parse_document("tests/fixtures/mixed-left.arxml").expect("parse");
+    assert_eq!(doc.prolog, vec![ContentNode::Comment(" generated by ToolX 1.2 ".to_string())]);
+
+    let pkg = &doc.root.children[0].children[0];
+    // indentation between the children is kept as blank text
+    let nodes: Vec<_> = pkg.content.iter()
+        .map(|c| &c.node)
+        .filter(|n| !matches!(n, ContentNode::Text(t) if t.trim().is_empty()))
+        .collect();
+    assert_eq!(nodes, vec![
+        &ContentNode::ShortName,
+        &ContentNode::Comment(" BEGIN GENERATED ".to_string()),
+        &ContentNode::ProcessingInstruction("tool-marker keep".to_string()),
+    ]);
+    let l1 = find(find(find(pkg, "INTRODUCTION"), "P"), "L-1");
+    assert_eq!(l1.content[0].node, ContentNode::CData("a < b".to_string()));
+
+    let xml = doc.serialize();
+    assert!(xml.contains("<!-- generated by ToolX 1.2 -->\n<AUTOSAR"));
+    assert!(xml.contains("<SHORT-NAME>Pkg</SHORT-NAME>\n      <!-- BEGIN GENERATED -->\n      <DESC>"));
+    assert!(xml.contains("<L-1 L=\"EN\"><![CDATA[a < b]]></L-1>"));
+    assert!(xml.contains("</INTRODUCTION>\n      <?tool-marker keep?>\n    </AR-PACKAGE>"));
+
+    let path = std::env::temp_dir().join(format!("arxml-diff-mixed-{}.arxml", std::process::id()));
+    std::fs::write(&path, &xml).expect("write");
+    let again = parse_document(path.to_str().unwrap()).expect("reparse");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(again, doc);
+}
+
+#[test]
+fn comments_and_cdata_markup_are_ignored_by_default() {
+    let left = parse_file("tests/fixtures/mixed-left.arxml").expect("parse left");
+    let right = parse_file("tests/fixtures/mixed-right.arxml").expect("parse right");
+
+    let diff = compute_tree_diff(&left, &right);
+    let pkg = &diff.children[0].children[0];
+    assert_eq!(find_diff(pkg, "INTRODUCTION").status, NodeStatus::Unchanged);
+    // moving the inline <E> within the sentence is a change of the L-2 itself
+    let l2 = find_diff(find_diff(pkg, "DESC"), "L-2");
+    assert_eq!(l2.status, NodeStatus::Changed);
+    assert!(l2.children.iter().all(|c| c.status == NodeStatus::Unchanged));
+}
+
+#[test]
+fn include_comments_diffs_comments_and_cdata() {
+    let left = parse_file("tests/fixtures/mixed-left.arxml").expect("parse left");
+    let right = parse_file("tests/fixtures/mixed-right.arxml").expect("parse right");
+
+    let opts = DiffOptions::builder().include_comments(true).build();
+    let diff = compute_tree_diff_with(&left, &right, &opts);
+    let pkg = &diff.children[0].children[0];
+    assert_eq!(pkg.status, NodeStatus::Changed);
+    assert_eq!(find_diff(pkg, "INTRODUCTION").status, NodeStatus::Changed);
+
+    // identical documents stay unchanged with comments included
+    assert_eq!(compute_tree_diff_with(&left, &left, &opts).status, NodeStatus::Unchanged);
+}
@@ -0,0 +1,90 @@
+use arxml_diff::error::Error;
+use arxml_diff::parse::{parse_document, parse_document_with, parse_reader_with, parse_str_with, Diagnostic, ParseOptions};
+
+fn strict_error(path: &str) -> Diagnostic {
+    let err = parse_document(path).expect_err("strict parse should fail");
+    match err {
+        Error::Parse(d) => d,
+        other => panic!("expected a parse error, got {}", other),
+    }
+}
+
+#[test]
+fn strict_errors_carry_location_snippet_and_open_elements() {
+    let d = strict_error("tests/fixtures/malformed.arxml");
+    assert_eq!(d.path, "tests/fixtures/malformed.arxml");
+    assert_eq!((d.line, d.column), (8, 30));
+    assert_eq!(d.snippet, "          <SHORT-NAME>SpeedIf</SHORT-NAM>");
+    assert_eq!(d.open_elements, vec!["AUTOSAR", "AR-PACKAGES", "AR-PACKAGE", "ELEMENTS", "SENDER-RECEIVER-INTERFACE", "SHORT-NAME"]);
+    let shown = d.to_string();
+    assert!(shown.starts_with("tests/fixtures/malformed.arxml:8:30: "), "{}", shown);
+    assert!(shown.contains("in AUTOSAR > AR-PACKAGES"), "{}", shown);
+}
+
+#[test]
+fn truncated_file_reports_unclosed_elements() {
+    let d = strict_error("tests/fixtures/truncated.arxml");
+    assert_eq!(d.line, 8);
+    assert!(d.message.contains("5 element(s) still open"), "{}", d.message);
+    assert_eq!(d.open_elements.last().map(String::as_str), Some("SENDER-RECEIVER-INTERFACE"));
+}
+
+#[test]
+fn missing_file_names_the_path() {
+    let err = parse_document("tests/fixtures/does-not-exist.arxml").expect_err("missing file");
+    assert!(err.to_string().contains("tests/fixtures/does-not-exist.arxml"));
+}
+
+#[test]
+fn lenient_mode_recovers_and_reports_warnings() {
+    let opts = ParseOptions { lenient: true };
+    let doc = parse_document_with("tests/fixtures/malformed.arxml", &opts).expect("lenient parse");
+    let messages: Vec<_> = doc.warnings.iter().map(|w| (w.line, w.message.as_str())).collect();
+    assert_eq!(messages.len(), 3, "{:?}", messages);
+    assert_eq!(messages[0], (8, "unexpected end tag </SHORT-NAM> ignored"));
+    assert_eq!(messages[1].0, 9);
+    // at the entity, not at the start of the text around it
+    assert_eq!(doc.warnings[1].column, 32);
+    assert_eq!(messages[2], (10, "<SHORT-NAME> implicitly closed by </SENDER-RECEIVER-INTERFACE>"));
+
+    // the tree is still usable: the interface and its package survive
+    let pkg = &doc.root.children[0].children[0];
+    assert_eq!(pkg.short_name.as_deref(), Some("Pkg"));
+    assert_eq!(pkg.children[0].children[0].tag, "SENDER-RECEIVER-INTERFACE");
+    assert_eq!(pkg.children.len(), 1);
+
+    let doc = parse_document_with("tests/fixtures/truncated.arxml", &opts).expect("lenient parse");
+    assert_eq!(doc.warnings.len(), 1);
+    let iface = &doc.root.children[0].children[0].children[0].children[0];
+    assert_eq!(iface.short_name.as_deref(), Some("SpeedIf"));
+}
+
+#[test]
+fn content_after_the_root_element_is_an_error() {
+    for (xml, line) in [
+        ("<AUTOSAR/>\n<!-- ok -->\n<AUTOSAR/>", 3),
+        ("<AUTOSAR></AUTOSAR>\n</AUTOSAR>", 2),
+        ("<AUTOSAR></AUTOSAR>\n<!-- unterminated", 2),
+    ] {
+        match parse_str_with(xml, &ParseOptions::default()).expect_err(xml) {
+            Error::Parse(d) => assert_eq!(d.line, line, "{}: {}", xml, d),
+            other => panic!("expected a parse error, got {}", other),
+        }
+
+        let doc = parse_str_with(xml, &ParseOptions { lenient: true }).expect(xml);
+        assert_eq!(doc.root.tag, "AUTOSAR");
+        assert_eq!(doc.warnings.iter().map(|w| w.line).collect::<Vec<_>>(), vec![line], "{}", xml);
+    }
+}
+
+#[test]
+fn columns_and_snippets_follow_the_source_encoding() {
+    let mut xml = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<AUTOSAR><A>caf".to_vec();
+    xml.extend_from_slice(b"\xb0 \xbd &bad; x</A></AUTOSAR>");
+    let d = match parse_reader_with(xml.as_slice(), &ParseOptions::default()).expect_err("bad entity") {
+        Error::Parse(d) => d,
+        other => panic!("expected a parse error, got {}", other),
+    };
+    assert_eq!((d.line, d.column), (2, 20));
+    assert_eq!(d.snippet, "<AUTOSAR><A>caf\u{b0} \u{bd} &bad; x</A></AUTOSAR>");
+}
@@ -1,6 +1,7 @@
+#![cfg(feature = "autosar")]
+
 use std::str::FromStr;
 
-#[cfg(feature = "autosar")]
 use autosar_data::ElementName;
 
 #[test]
@@ -0,0 +1,46 @@
+use arxml_diff::parse::{parse_document, Element};
+use arxml_diff::diff::{compute_tree_diff, document_notes, NodeStatus};
+
+fn desc_text(root: &Element) -> String {
+    let swc = &root.children[0].children[0].children[0].children[0];
+    swc.children[0].children[0].text().expect("L-2 text")
+}
+
+#[test]
+fn utf16_with_bom_is_decoded() {
+    let doc = parse_document("tests/fixtures/utf16le.arxml").expect("parse utf-16");
+    assert_eq!(doc.encoding, "UTF-16LE");
+    assert_eq!(desc_text(&doc.root), "Geschwindigkeit < 120 km/h → Ü & rising");
+
+    let utf8 = parse_document("tests/fixtures/utf8-umlaut.arxml").expect("parse utf-8");
+    assert_eq!(utf8.encoding, "UTF-8");
+    assert_eq!(compute_tree_diff(&doc.root, &utf8.root).status, NodeStatus::Unchanged);
+}
+
+#[test]
+fn declared_latin1_is_decoded() {
+    let doc = parse_document("tests/fixtures/latin1.arxml").expect("parse latin-1");
+    assert_eq!(doc.encoding, "windows-1252");
+    assert_eq!(desc_text(&doc.root), "Geschwindigkeit < 120 km/h, Ü & rising");
+}
+
+#[test]
+fn different_encodings_are_reported() {
+    let left = parse_document("tests/fixtures/utf16le.arxml").expect("parse left");
+    let right = parse_document("tests/fixtures/utf8-umlaut.arxml").expect("parse right");
+    assert_eq!(document_notes(&left, &right), vec!["input encoding differs: UTF-16LE vs UTF-8".to_string()]);
+    assert!(document_notes(&right, &right).is_empty());
+}
+
+#[test]
+fn to_bytes_writes_the_source_encoding() {
+    for fixture in ["utf16le", "latin1"] {
+        let path = format!("tests/fixtures/{}.arxml", fixture);
+        let doc = parse_document(&path).expect("parse");
+        let out = std::env::temp_dir().join(format!("arxml-diff-enc-{}-{}.arxml", fixture, std::process::id()));
+        std::fs::write(&out, doc.to_bytes()).expect("write");
+        let again = parse_document(out.to_str().unwrap()).expect("reparse");
+        let _ = std::fs::remove_file(&out);
+        assert_eq!(again, doc, "round trip of {}", fixture);
+    }
+}
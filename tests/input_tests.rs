@@ -0,0 +1,107 @@
+use arxml_diff::diff::{compute_multi_diff, DiffOptions, NodeStatus};
+use arxml_diff::input::{is_collection, read_collection};
+use arxml_diff::parse::parse_file;
+use std::io::Write;
+use std::path::PathBuf;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("arxml-diff-input-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/fixtures/{}", name)).expect("read fixture")
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+fn write_zip(path: &PathBuf, entries: &[(&str, Vec<u8>)]) {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn compressed_files_are_read_transparently() {
+    let dir = scratch_dir("compressed");
+    let plain = parse_file("tests/fixtures/sample-left.arxml").expect("parse plain");
+
+    let gz = dir.join("sample.arxml.gz");
+    std::fs::write(&gz, gzip(&fixture("sample-left.arxml"))).unwrap();
+    assert_eq!(parse_file(gz.to_str().unwrap()).expect("parse gzip"), plain);
+
+    let zst = dir.join("sample.arxml.zst");
+    std::fs::write(&zst, zstd::encode_all(&fixture("sample-left.arxml")[..], 3).unwrap()).unwrap();
+    assert_eq!(parse_file(zst.to_str().unwrap()).expect("parse zstd"), plain);
+}
+
+#[test]
+fn zip_entries_are_addressed_with_bang_separator() {
+    let dir = scratch_dir("entry");
+    let bundle = dir.join("bundle.zip");
+    write_zip(&bundle, &[
+        ("path/System.arxml", fixture("sample-left.arxml")),
+        ("path/Other.arxml.gz", gzip(&fixture("sample-right.arxml"))),
+    ]);
+    let bundle = bundle.to_str().unwrap();
+
+    let system = parse_file(&format!("{}!/path/System.arxml", bundle)).expect("parse entry");
+    assert_eq!(system, parse_file("tests/fixtures/sample-left.arxml").unwrap());
+    let other = parse_file(&format!("{}!/path/Other.arxml.gz", bundle)).expect("parse compressed entry");
+    assert_eq!(other, parse_file("tests/fixtures/sample-right.arxml").unwrap());
+
+    let err = parse_file(&format!("{}!/missing.arxml", bundle)).expect_err("missing entry");
+    assert!(err.to_string().contains("missing.arxml"));
+}
+
+#[test]
+fn directories_and_archives_compare_as_collections() {
+    let dir = scratch_dir("collections");
+    let left = dir.join("left");
+    std::fs::create_dir_all(left.join("sub")).unwrap();
+    std::fs::write(left.join("Common.arxml"), fixture("sample-left.arxml")).unwrap();
+    std::fs::write(left.join("sub/Gone.arxml"), fixture("sample-left.arxml")).unwrap();
+    std::fs::write(left.join("notes.txt"), b"not arxml").unwrap();
+
+    let right = dir.join("right.zip");
+    write_zip(&right, &[
+        ("Common.arxml.gz", gzip(&fixture("sample-right.arxml"))),
+        ("New.arxml", fixture("sample-right.arxml")),
+    ]);
+
+    let (left, right) = (left.to_str().unwrap(), right.to_str().unwrap());
+    assert!(is_collection(left) && is_collection(right));
+    assert!(!is_collection("tests/fixtures/sample-left.arxml"));
+    assert!(!is_collection(&format!("{}!/New.arxml", right)));
+
+    let parse_all = |spec: &str| -> Vec<(String, arxml_diff::parse::Element)> {
+        read_collection(spec).expect("read collection").into_iter()
+            .map(|m| {
+                let path = std::env::temp_dir().join(format!("arxml-diff-member-{}", std::process::id()));
+                std::fs::write(&path, &m.data).unwrap();
+                (m.key, parse_file(path.to_str().unwrap()).expect("parse member"))
+            })
+            .collect()
+    };
+    let (l, r) = (parse_all(left), parse_all(right));
+    let keys: Vec<_> = l.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, vec!["Common.arxml", "sub/Gone.arxml"]);
+
+    let diff = compute_multi_diff(&l, &r, &DiffOptions::default());
+    let files: Vec<_> = diff.children.iter().map(|c| (c.key.as_deref().unwrap(), c.status)).collect();
+    assert_eq!(files, vec![
+        ("Common.arxml", NodeStatus::Changed),
+        ("New.arxml", NodeStatus::Added),
+        ("sub/Gone.arxml", NodeStatus::Removed),
+    ]);
+    assert_eq!(diff.status, NodeStatus::Changed);
+}
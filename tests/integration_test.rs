@@ -24,3 +24,14 @@ fn short_name_extraction() {
     let comp_names_r: Vec<_> = components_r.children.iter().filter_map(|c| c.short_name.clone()).collect();
     assert_eq!(comp_names_r, vec!["CompB".to_string(), "CompC".to_string()]);
 }
+
+#[test]
+fn empty_elements_are_kept() {
+    let path = std::env::temp_dir().join(format!("arxml-diff-empty-{}.arxml", std::process::id()));
+    std::fs::write(&path, "<AUTOSAR><ADMIN-DATA/><ECU><SHORT-NAME>Ecu</SHORT-NAME><PORTS/></ECU></AUTOSAR>").unwrap();
+    let root = parse_file(path.to_str().unwrap()).expect("parse");
+    let _ = std::fs::remove_file(&path);
+    let tags: Vec<_> = root.children.iter().map(|c| c.tag.as_str()).collect();
+    assert_eq!(tags, vec!["ADMIN-DATA", "ECU"]);
+    assert_eq!(root.children[1].children[0].tag, "PORTS");
+}
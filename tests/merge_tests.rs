@@ -0,0 +1,204 @@
+use arxml_diff::diff::{compute_tree_diff, compute_tree_diff_with, DiffNode, DiffOptions};
+use arxml_diff::merge::{default_merge_output, merge, MergeError, Take};
+use arxml_diff::parse::{parse_str, Document};
+use arxml_diff::tui::App;
+
+mod common;
+
+use common::{press, screen, shows};
+
+const LEFT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>
+<I-SIGNAL UUID="1"><SHORT-NAME>Speed</SHORT-NAME><LENGTH>8</LENGTH></I-SIGNAL>
+<I-SIGNAL UUID="2"><SHORT-NAME>Old</SHORT-NAME><LENGTH>4</LENGTH></I-SIGNAL>
+<I-SIGNAL UUID="3"><SHORT-NAME>Gear</SHORT-NAME><ADMIN-DATA>left</ADMIN-DATA></I-SIGNAL>
+</ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>"#;
+
+const RIGHT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>
+<I-SIGNAL UUID="1"><SHORT-NAME>Speed</SHORT-NAME><LENGTH>16</LENGTH></I-SIGNAL>
+<!-- new signals below -->
+<I-SIGNAL UUID="3"><SHORT-NAME>Gear</SHORT-NAME><ADMIN-DATA>right</ADMIN-DATA><LENGTH>2</LENGTH></I-SIGNAL>
+<I-SIGNAL UUID="4"><SHORT-NAME>Torque</SHORT-NAME><LENGTH>12</LENGTH></I-SIGNAL>
+</ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>
+<!-- end of export -->"#;
+
+fn docs() -> (Document, Document) {
+    (parse_str(LEFT).expect("parse left"), parse_str(RIGHT).expect("parse right"))
+}
+
+/// Child indices of the first node (in document order) with the AUTOSAR `path` and
+/// `tag`.
+fn indices_of(node: &DiffNode, path: &str, tag: &str) -> Option<Vec<usize>> {
+    if node.path == path && node.tag == tag {
+        return Some(Vec::new());
+    }
+    node.children.iter().enumerate().find_map(|(i, c)| {
+        let mut found = indices_of(c, path, tag)?;
+        found.insert(0, i);
+        Some(found)
+    })
+}
+
+fn text_of(doc: &Document, signal: &str, tag: &str) -> Option<String> {
+    let elements = &doc.root.children[0].children[0].children[0];
+    let signal = elements.children.iter().find(|e| e.short_name.as_deref() == Some(signal))?;
+    signal.children.iter().find(|e| e.tag == tag)?.text()
+}
+
+#[test]
+fn taking_one_side_everywhere_gives_that_side() {
+    let (left, right) = docs();
+    // the comment differs as well
+    let opts = DiffOptions::builder().include_comments(true).build();
+    let diff = compute_tree_diff_with(&left.root, &right.root, &opts);
+    assert_eq!(merge(&left, &right, &diff, &opts, |_| Take::Right).unwrap().root, right.root);
+    assert_eq!(merge(&left, &right, &diff, &opts, |_| Take::Left).unwrap().root, left.root);
+}
+
+#[test]
+fn merges_each_change_from_the_side_taken() {
+    let (left, right) = docs();
+    let diff = compute_tree_diff(&left.root, &right.root);
+    let speed_length = indices_of(&diff, "/Pkg/Speed", "LENGTH").expect("Speed LENGTH");
+    let old = indices_of(&diff, "/Pkg/Old", "I-SIGNAL").expect("Old");
+    let torque = indices_of(&diff, "/Pkg/Torque", "I-SIGNAL").expect("Torque");
+    let take = |indices: &[usize]| if [&speed_length, &old, &torque].iter().any(|i| i.as_slice() == indices) { Take::Left } else { Take::Right };
+    let merged = merge(&left, &right, &diff, &DiffOptions::default(), take).unwrap();
+
+    // the left LENGTH, the removed signal kept, the added one left out
+    assert_eq!(text_of(&merged, "Speed", "LENGTH").as_deref(), Some("8"));
+    assert_eq!(text_of(&merged, "Old", "LENGTH").as_deref(), Some("4"));
+    assert_eq!(text_of(&merged, "Gear", "LENGTH").as_deref(), Some("2"));
+    assert!(text_of(&merged, "Torque", "SHORT-NAME").is_none());
+
+    // written faithfully: declaration, comments and attributes of the right side
+    let xml = merged.serialize();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<AUTOSAR>"), "{}", xml);
+    assert!(xml.ends_with("</AUTOSAR>\n<!-- end of export -->"), "{}", xml);
+    assert!(xml.contains("<LENGTH>8</LENGTH></I-SIGNAL>\n<I-SIGNAL UUID=\"2\"><SHORT-NAME>Old</SHORT-NAME><LENGTH>4</LENGTH></I-SIGNAL>\n<!-- new signals below -->\n<I-SIGNAL UUID=\"3\">"), "{}", xml);
+    assert!(xml.contains("<LENGTH>2</LENGTH></I-SIGNAL>\n</ELEMENTS>"), "{}", xml);
+    let reparsed = parse_str(&xml).expect("merged output parses");
+    assert_eq!(reparsed.root, merged.root);
+}
+
+#[test]
+fn ignored_elements_come_from_the_side_of_their_parent() {
+    let (left, right) = docs();
+    let opts = DiffOptions::builder().ignore_tag("ADMIN-DATA").build();
+    let diff = compute_tree_diff_with(&left.root, &right.root, &opts);
+    let merged = merge(&left, &right, &diff, &opts, |_| Take::Left).unwrap();
+    // Gear has no changes of its own, so it is built on the right side
+    assert_eq!(text_of(&merged, "Gear", "ADMIN-DATA").as_deref(), Some("right"));
+    assert!(text_of(&merged, "Gear", "LENGTH").is_none());
+}
+
+#[test]
+fn merged_output_keeps_the_indentation_of_the_inputs() {
+    let left = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR>
+  <AR-PACKAGES>
+    <AR-PACKAGE>
+      <SHORT-NAME>Pkg</SHORT-NAME>
+      <ELEMENTS>
+        <I-SIGNAL UUID="1">
+          <SHORT-NAME>Speed</SHORT-NAME>
+          <LENGTH>8</LENGTH>
+        </I-SIGNAL>
+        <I-SIGNAL UUID="2">
+          <SHORT-NAME>Old</SHORT-NAME>
+        </I-SIGNAL>
+      </ELEMENTS>
+    </AR-PACKAGE>
+  </AR-PACKAGES>
+</AUTOSAR>"#;
+    let right = r#"<?xml version="1.0" encoding="UTF-8"?>
+<AUTOSAR>
+  <AR-PACKAGES>
+    <AR-PACKAGE>
+      <SHORT-NAME>Pkg</SHORT-NAME>
+      <ELEMENTS>
+        <I-SIGNAL UUID="1">
+          <SHORT-NAME>Speed</SHORT-NAME>
+          <LENGTH>16</LENGTH>
+        </I-SIGNAL>
+        <I-SIGNAL UUID="3">
+          <SHORT-NAME>New</SHORT-NAME>
+        </I-SIGNAL>
+      </ELEMENTS>
+    </AR-PACKAGE>
+  </AR-PACKAGES>
+</AUTOSAR>"#;
+    let (left_doc, right_doc) = (parse_str(left).expect("parse left"), parse_str(right).expect("parse right"));
+    let diff = compute_tree_diff(&left_doc.root, &right_doc.root);
+    let opts = DiffOptions::default();
+    assert_eq!(merge(&left_doc, &right_doc, &diff, &opts, |_| Take::Right).unwrap().serialize(), right);
+
+    assert_eq!(merge(&left_doc, &right_doc, &diff, &opts, |_| Take::Left).unwrap().serialize(), left);
+
+    // the removed signal kept next to the added one, each on its own lines
+    let old = indices_of(&diff, "/Pkg/Old", "I-SIGNAL").expect("Old");
+    let merged = merge(&left_doc, &right_doc, &diff, &opts, |i| if i == old.as_slice() { Take::Left } else { Take::Right }).unwrap().serialize();
+    let old_signal = "        <I-SIGNAL UUID=\"2\">\n          <SHORT-NAME>Old</SHORT-NAME>\n        </I-SIGNAL>\n";
+    assert_eq!(merged, right.replace("        <I-SIGNAL UUID=\"3\">", &format!("{}        <I-SIGNAL UUID=\"3\">", old_signal)));
+}
+
+#[test]
+fn different_roots_are_taken_from_one_side() {
+    let (left, right) = (parse_str("<AUTOSAR/>").unwrap(), parse_str("<OTHER/>").unwrap());
+    let diff = compute_tree_diff(&left.root, &right.root);
+    let opts = DiffOptions::default();
+    assert_eq!(merge(&left, &right, &diff, &opts, |_| Take::Left).unwrap().root, left.root);
+    assert_eq!(merge(&left, &right, &diff, &opts, |_| Take::Right).unwrap().root, right.root);
+    // keeping the removed root and the added one, or neither, is rejected
+    for removed in [Take::Left, Take::Right] {
+        let added = if removed == Take::Left { Take::Right } else { Take::Left };
+        let take = |i: &[usize]| if i == [0] { removed } else { added };
+        assert_eq!(merge(&left, &right, &diff, &opts, take).unwrap_err(), MergeError::RootConflict);
+    }
+}
+
+#[test]
+fn default_output_is_next_to_the_right_input() {
+    assert_eq!(default_merge_output("out/System.arxml").to_str(), Some("out/System.merged.arxml"));
+    assert_eq!(default_merge_output("System.arxml.gz").to_str(), Some("System.merged.arxml"));
+    assert_eq!(default_merge_output("bundle.zip!/a/System.arxml").to_str(), Some("bundle.merged.arxml"));
+}
+
+#[test]
+fn tui_takes_sides_and_writes_the_merge() {
+    let (left, right) = docs();
+    let diff = compute_tree_diff(&left.root, &right.root);
+    let output = std::env::temp_dir().join(format!("arxml-diff-merge-{}.arxml", std::process::id()));
+    let mut app = App::new(&diff, &[]).with_merge(&left, &right, DiffOptions::default(), &output);
+    let shown = screen(&mut app).join("\n");
+    assert!(shown.contains("merge: 0 left, 0 right, 5 undecided"), "{}", shown);
+
+    // keep the left LENGTH of Speed, then the removed signal Old
+    press(&mut app, "E]<");
+    assert_eq!(app.selected().tag, "LENGTH");
+    press(&mut app, "]<");
+    assert_eq!(app.selected().key.as_deref(), Some("Old"));
+    let shown = screen(&mut app).join("\n");
+    assert!(shown.contains("merge: 2 left, 0 right, 3 undecided"), "{}", shown);
+    assert!(shown.contains("Old ◀"), "{}", shown);
+
+    // } takes the right side of everything below ELEMENTS, and again undoes it
+    press(&mut app, "h");
+    while app.selected().tag != "ELEMENTS" {
+        press(&mut app, "h");
+    }
+    press(&mut app, "}");
+    assert!(shows(&mut app, "merge: 0 left, 5 right, 0 undecided"));
+    press(&mut app, "}");
+    assert!(shows(&mut app, "merge: 0 left, 0 right, 5 undecided"));
+    press(&mut app, "]<]<w");
+
+    assert_eq!(app.status().map(|s| s.starts_with("Merged ARXML written to")), Some(true));
+    let written = parse_str(&std::fs::read_to_string(&output).expect("merged file")).expect("parse merged file");
+    assert_eq!(written.root, app.merged().expect("merge mode").expect("merged document").root);
+    assert_eq!(text_of(&written, "Speed", "LENGTH").as_deref(), Some("8"));
+    assert_eq!(text_of(&written, "Old", "LENGTH").as_deref(), Some("4"));
+    assert_eq!(text_of(&written, "Torque", "LENGTH").as_deref(), Some("12"));
+    std::fs::remove_file(&output).ok();
+}
@@ -0,0 +1,47 @@
+use arxml_diff::parse::{parse_document, parse_file};
+use arxml_diff::diff::{compute_tree_diff, schema_notes, NodeStatus};
+
+#[test]
+fn elements_store_local_name_and_namespace() {
+    let root = parse_file("tests/fixtures/prefixed.arxml").expect("parse prefixed");
+    assert_eq!(root.tag, "AUTOSAR");
+    assert_eq!(root.namespace.as_deref(), Some("http://autosar.org/schema/r4.0"));
+    assert_eq!(root.prefix.as_deref(), Some("ar"));
+    let pkg = &root.children[0].children[0];
+    assert_eq!(pkg.tag, "AR-PACKAGE");
+    assert_eq!(pkg.short_name.as_deref(), Some("Pkg"));
+
+    let plain = parse_file("tests/fixtures/roundtrip.arxml").expect("parse plain");
+    assert_eq!(plain.namespace, root.namespace);
+    assert_eq!(plain.prefix, None);
+}
+
+#[test]
+fn prefix_choice_does_not_produce_changes() {
+    let left = parse_file("tests/fixtures/roundtrip.arxml").expect("parse left");
+    let right = parse_file("tests/fixtures/prefixed.arxml").expect("parse right");
+    let diff = compute_tree_diff(&left, &right);
+    assert_eq!(diff.status, NodeStatus::Unchanged);
+    assert_eq!(diff.tag, "AUTOSAR");
+    // equality is about the source, which is written differently
+    assert_ne!(left, right);
+}
+
+#[test]
+fn schema_version_difference_is_reported_as_note() {
+    let left = parse_file("tests/fixtures/roundtrip.arxml").expect("parse left");
+    let right = parse_file("tests/fixtures/prefixed.arxml").expect("parse right");
+    assert_eq!(
+        schema_notes(&left, &right),
+        vec!["AUTOSAR schema version differs: AUTOSAR_00051 vs AUTOSAR_00049".to_string()]
+    );
+    assert!(schema_notes(&left, &left).is_empty());
+}
+
+#[test]
+fn prefixed_document_round_trips_with_its_prefixes() {
+    let doc = parse_document("tests/fixtures/prefixed.arxml").expect("parse");
+    let xml = doc.serialize();
+    assert!(xml.contains("<ar:AR-PACKAGE T=\"2024-01-01T00:00:00Z\" UUID=\"pkg-1\">\n      <ar:SHORT-NAME>Pkg</ar:SHORT-NAME>"));
+    assert!(xml.ends_with("</ar:AUTOSAR>"));
+}
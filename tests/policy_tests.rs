@@ -0,0 +1,120 @@
+use arxml_diff::diff::{compute_tree_diff, ChangeKind, DiffNode, NodeStatus, ValueChange};
+use arxml_diff::parse::parse_str;
+use arxml_diff::policy::{Policy, Severity};
+use std::process::Command;
+
+mod common;
+
+use common::package_doc;
+
+fn rated(left: &str, right: &str, policy: &Policy) -> DiffNode {
+    let l = parse_str(&package_doc(left)).expect("parse left");
+    let r = parse_str(&package_doc(right)).expect("parse right");
+    let mut diff = compute_tree_diff(&l.root, &r.root);
+    policy.apply(&mut diff);
+    diff
+}
+
+/// `(path, tag, severity)` of every rated change, in document order.
+fn severities(node: &DiffNode) -> Vec<(String, String, Severity)> {
+    let mut out = Vec::new();
+    if let Some(s) = node.severity {
+        out.push((node.path.clone(), node.tag.clone(), s));
+    }
+    for c in &node.children {
+        out.extend(severities(c));
+    }
+    out
+}
+
+const IF: &str = "<SENDER-RECEIVER-INTERFACE><SHORT-NAME>SpeedIf</SHORT-NAME><DESC><L-2 L=\"EN\">Speed</L-2></DESC><DATA-ELEMENTS><VARIABLE-DATA-PROTOTYPE><SHORT-NAME>Speed</SHORT-NAME><TYPE-TREF DEST=\"IMPLEMENTATION-DATA-TYPE\">/Types/UInt8</TYPE-TREF></VARIABLE-DATA-PROTOTYPE></DATA-ELEMENTS></SENDER-RECEIVER-INTERFACE>";
+const SWC: &str = "<APPLICATION-SW-COMPONENT-TYPE><SHORT-NAME>Swc</SHORT-NAME><CATEGORY>A</CATEGORY></APPLICATION-SW-COMPONENT-TYPE>";
+
+#[test]
+fn autosar_policy_rates_breaking_changes_as_errors() {
+    let policy = Policy::autosar();
+
+    let removed = rated(&format!("{}{}", IF, SWC), SWC, &policy);
+    assert_eq!(severities(&removed), vec![
+        ("/Pkg/SpeedIf".to_string(), "SENDER-RECEIVER-INTERFACE".to_string(), Severity::Error),
+    ]);
+    assert_eq!(removed.max_severity(), Some(Severity::Error));
+
+    let added = rated(SWC, &format!("{}{}", IF, SWC), &policy);
+    assert_eq!(added.max_severity(), Some(Severity::Info));
+
+    let retyped = IF.replace("/Types/UInt8", "/Types/UInt16");
+    let diff = rated(IF, &retyped, &policy);
+    assert_eq!(severities(&diff), vec![
+        ("/Pkg/SpeedIf/Speed".to_string(), "TYPE-TREF".to_string(), Severity::Error),
+    ]);
+
+    let redocumented = IF.replace(">Speed</L-2>", ">Vehicle speed</L-2>");
+    assert_eq!(rated(IF, &redocumented, &policy).max_severity(), Some(Severity::Info));
+
+    let recategorized = SWC.replace(">A<", ">B<");
+    assert_eq!(rated(SWC, &recategorized, &policy).max_severity(), Some(Severity::Warning));
+}
+
+#[test]
+fn value_changes_are_recorded() {
+    let diff = rated(IF, &IF.replace("/Types/UInt8", "/Types/UInt16").replace("IMPLEMENTATION-DATA-TYPE", "APPLICATION-PRIMITIVE-DATA-TYPE"), &Policy::autosar());
+    fn find<'a>(n: &'a DiffNode, tag: &str) -> Option<&'a DiffNode> {
+        if n.tag == tag { return Some(n); }
+        n.children.iter().find_map(|c| find(c, tag))
+    }
+    let tref = find(&diff, "TYPE-TREF").expect("TYPE-TREF");
+    assert_eq!(tref.status, NodeStatus::Changed);
+    assert_eq!(tref.changes, vec![
+        ValueChange { what: "@DEST".to_string(), left: Some("IMPLEMENTATION-DATA-TYPE".to_string()), right: Some("APPLICATION-PRIMITIVE-DATA-TYPE".to_string()) },
+        ValueChange { what: "text".to_string(), left: Some("/Types/UInt8".to_string()), right: Some("/Types/UInt16".to_string()) },
+    ]);
+    // ancestors changed through their children only
+    assert!(find(&diff, "SENDER-RECEIVER-INTERFACE").unwrap().changes.is_empty());
+    assert_eq!(diff.summary().counts.changed, 1);
+}
+
+#[test]
+fn custom_rules_match_tag_path_and_kind() {
+    let policy = Policy::from_json(r#"{
+        "default": "warning",
+        "rules": [
+            { "path": "/Pkg/*If", "kind": ["removed"], "severity": "error" },
+            { "tag": "APPLICATION-*", "severity": "info" }
+        ]
+    }"#).expect("valid policy");
+    assert_eq!(policy.severity(&["SENDER-RECEIVER-INTERFACE"], "/Pkg/SpeedIf", ChangeKind::Removed), Severity::Error);
+    assert_eq!(policy.severity(&["SENDER-RECEIVER-INTERFACE"], "/Pkg/Sub/SpeedIf", ChangeKind::Removed), Severity::Warning);
+    assert_eq!(policy.severity(&["SENDER-RECEIVER-INTERFACE"], "/Pkg/SpeedIf", ChangeKind::Added), Severity::Warning);
+    assert_eq!(policy.severity(&["APPLICATION-SW-COMPONENT-TYPE"], "/Pkg/Swc", ChangeKind::Removed), Severity::Info);
+
+    let deep = Policy::from_json(r#"{ "rules": [{ "path": "/Pkg/**", "severity": "error" }] }"#).unwrap();
+    assert_eq!(deep.severity(&["X"], "/Pkg/Sub/SpeedIf", ChangeKind::Changed), Severity::Error);
+    assert_eq!(deep.severity(&["X"], "/Other/SpeedIf", ChangeKind::Changed), Severity::Info);
+
+    assert!(Policy::from_json(r#"{ "rules": [{ "severity": "fatal" }] }"#).is_err());
+    assert!(Policy::from_json(r#"{ "rules": [{ "tags": "X", "severity": "info" }] }"#).is_err());
+}
+
+#[test]
+fn fail_on_sets_the_exit_status() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_arxml-diff"))
+            .args(args)
+            .args(["tests/fixtures/sample-left.arxml", "tests/fixtures/sample-right.arxml"])
+            .output().expect("run arxml-diff")
+    };
+    // the sample removes one component (warning) and adds one (info)
+    assert_eq!(run(&["--quiet"]).status.code(), Some(1));
+    assert_eq!(run(&["--quiet", "--fail-on", "warning"]).status.code(), Some(1));
+    assert_eq!(run(&["--quiet", "--fail-on", "error"]).status.code(), Some(0));
+
+    let policy = std::env::temp_dir().join(format!("arxml-diff-policy-{}.json", std::process::id()));
+    std::fs::write(&policy, r#"{ "rules": [{ "kind": ["removed"], "severity": "error" }] }"#).unwrap();
+    let out = run(&["--quiet", "--fail-on", "error", "--policy", policy.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    let _ = std::fs::remove_file(&policy);
+
+    let out = run(&["--policy", "tests/fixtures/does-not-exist.json"]);
+    assert_eq!(out.status.code(), Some(2));
+}
@@ -0,0 +1,130 @@
+use arxml_diff::diff::{compute_tree_diff, DiffNode};
+use arxml_diff::parse::parse_str;
+use arxml_diff::policy::Policy;
+use arxml_diff::render::{display_width, ColorChoice, SideBySideRenderer, TreeRenderer};
+use std::process::Command;
+
+mod common;
+
+use common::{render, sample_diff};
+
+#[test]
+fn tree_snapshot() {
+    insta::assert_snapshot!(render(&TreeRenderer { color: false, context: None }, &sample_diff()));
+}
+
+#[test]
+fn tree_colored_snapshot() {
+    insta::assert_snapshot!(render(&TreeRenderer { color: true, context: None }, &sample_diff()));
+}
+
+#[test]
+fn side_by_side_snapshot() {
+    insta::assert_snapshot!(render(&SideBySideRenderer { color: false, context: None, width: 80, wrap: false }, &sample_diff()));
+}
+
+#[test]
+fn side_by_side_colored_snapshot() {
+    insta::assert_snapshot!(render(&SideBySideRenderer { color: true, context: None, width: 80, wrap: false }, &sample_diff()));
+}
+
+/// A package of 20 signals where `Sig05` and `Sig15` differ.
+fn wide_diff() -> DiffNode {
+    let doc = |changed: &str| {
+        let signals: String = (0..20)
+            .map(|i| format!("<I-SIGNAL><SHORT-NAME>Sig{:02}</SHORT-NAME><LENGTH>{}</LENGTH></I-SIGNAL>", i, if i % 10 == 5 { changed } else { "8" }))
+            .collect();
+        format!("<AUTOSAR><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>{}</ELEMENTS></AR-PACKAGE></AUTOSAR>", signals)
+    };
+    let left = parse_str(&doc("8")).unwrap();
+    let right = parse_str(&doc("16")).unwrap();
+    let mut diff = compute_tree_diff(&left.root, &right.root);
+    Policy::autosar().apply(&mut diff);
+    diff
+}
+
+#[test]
+fn changes_only_snapshot() {
+    insta::assert_snapshot!(render(&TreeRenderer { color: false, context: Some(0) }, &wide_diff()));
+}
+
+#[test]
+fn context_snapshot() {
+    insta::assert_snapshot!(render(&TreeRenderer { color: false, context: Some(2) }, &wide_diff()));
+}
+
+#[test]
+fn side_by_side_context_snapshot() {
+    insta::assert_snapshot!(render(&SideBySideRenderer { color: false, context: Some(1), width: 80, wrap: false }, &wide_diff()));
+}
+
+fn speed_diff() -> DiffNode {
+    let doc = |speed: &str| parse_str(&format!(
+        "<AUTOSAR><AR-PACKAGE><SHORT-NAME>VehicleDynamicsConfigurationPackage</SHORT-NAME><ELEMENTS>\
+         <ECUC-NUMERICAL-PARAM-VALUE UUID=\"Speed\" T=\"{t}\">{speed}</ECUC-NUMERICAL-PARAM-VALUE>\
+         </ELEMENTS></AR-PACKAGE></AUTOSAR>",
+        speed = speed, t = if speed == "120" { "2024" } else { "2025" },
+    )).unwrap();
+    let mut diff = compute_tree_diff(&doc("120").root, &doc("130").root);
+    Policy::autosar().apply(&mut diff);
+    diff
+}
+
+#[test]
+fn side_by_side_shows_values() {
+    let out = render(&SideBySideRenderer { color: false, context: Some(0), width: 80, wrap: false }, &speed_diff());
+    let row = out.lines().find(|l| l.contains("Speed")).expect("Speed row");
+    assert!(row.starts_with("      Speed = 120 @T=2024 "), "{}", row);
+    assert!(row.ends_with(" │ ~ │       Speed = 130 @T=2025 [warning]"), "{}", row);
+}
+
+#[test]
+fn side_by_side_names_each_side_of_a_replaced_element() {
+    let left = parse_str("<A><SHORT-NAME>Old</SHORT-NAME></A>").unwrap();
+    let right = parse_str("<B><SHORT-NAME>New</SHORT-NAME></B>").unwrap();
+    let out = render(&SideBySideRenderer { color: false, context: None, width: 40, wrap: false }, &compute_tree_diff(&left.root, &right.root));
+    let rows: Vec<_> = out.lines().skip(2).map(|l| l.split_whitespace().collect::<Vec<_>>().join(" ")).collect();
+    assert_eq!(rows, vec!["Old │ ~ │ New", "Old │ - │", "│ + │ New"]);
+}
+
+#[test]
+fn side_by_side_truncates_and_wraps() {
+    let narrow = |wrap| SideBySideRenderer { color: true, context: None, width: 40, wrap };
+    let truncated = render(&narrow(false), &speed_diff());
+    assert!(truncated.contains("  VehicleDynami… │"), "{}", truncated);
+    // columns stay aligned although the cells carry colour codes
+    let widths: Vec<_> = truncated.lines().filter_map(|l| l.find('│').map(|i| display_width(&l[..i]))).collect();
+    assert!(widths.iter().all(|w| *w == widths[0]), "{:?}", widths);
+    insta::assert_snapshot!(render(&SideBySideRenderer { color: false, ..narrow(true) }, &speed_diff()));
+}
+
+#[test]
+fn display_width_ignores_escape_codes() {
+    assert_eq!(display_width("\u{1b}[38;5;9mCompA\u{1b}[39m"), 5);
+    assert_eq!(display_width("… 3 unchanged"), 13);
+    assert_eq!(display_width("速度"), 4);
+}
+
+#[test]
+fn color_policy() {
+    assert!(ColorChoice::Always.enabled());
+    assert!(!ColorChoice::Never.enabled());
+
+    let run = |args: &[&str], no_color: Option<&str>| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_arxml-diff"));
+        cmd.args(args).args(["tests/fixtures/sample-left.arxml", "tests/fixtures/sample-right.arxml"]);
+        match no_color {
+            Some(v) => cmd.env("NO_COLOR", v),
+            None => cmd.env_remove("NO_COLOR"),
+        };
+        String::from_utf8(cmd.output().expect("run arxml-diff").stdout).unwrap()
+    };
+    let has_escapes = |s: &str| s.contains('\u{1b}');
+
+    // stdout is a pipe here, so auto means no colour
+    assert!(!has_escapes(&run(&[], None)));
+    assert!(has_escapes(&run(&["--color", "always"], None)));
+    assert!(has_escapes(&run(&["--color", "always"], Some("1"))));
+    assert!(!has_escapes(&run(&["--color", "auto"], Some("1"))));
+    assert!(!has_escapes(&run(&["--color", "never"], None)));
+}
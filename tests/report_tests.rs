@@ -0,0 +1,76 @@
+use arxml_diff::diff::{compute_multi_diff, compute_tree_diff, DiffOptions};
+use arxml_diff::parse::{parse_file, parse_str};
+use arxml_diff::policy::{Policy, Severity};
+use arxml_diff::report::{JunitRenderer, PathsRenderer, SarifRenderer};
+
+mod common;
+
+use common::{render, sample_diff, LEFT, RIGHT};
+
+#[test]
+fn sarif_snapshot() {
+    let sarif = render(&SarifRenderer { left: LEFT.to_string(), right: RIGHT.to_string() }, &sample_diff());
+    let log: serde_json::Value = serde_json::from_str(&sarif).expect("valid JSON");
+    assert_eq!(log["runs"][0]["results"].as_array().map(Vec::len), Some(2));
+    insta::assert_snapshot!(sarif);
+}
+
+#[test]
+fn sarif_locations_are_uris() {
+    let uris = |left: &str, right: &str| -> Vec<String> {
+        let sarif = render(&SarifRenderer { left: left.to_string(), right: right.to_string() }, &sample_diff());
+        let log: serde_json::Value = serde_json::from_str(&sarif).unwrap();
+        log["runs"][0]["results"].as_array().unwrap().iter()
+            .map(|r| r["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(uris("old files/ECU #1.arxml", "/tmp/new 100%.arxml"), vec!["old%20files/ECU%20%231.arxml", "file:///tmp/new%20100%25.arxml"]);
+    assert_eq!(uris("C:\\exports\\left.arxml", "a:b.arxml"), vec!["file:///C:/exports/left.arxml", "a%3Ab.arxml"]);
+}
+
+#[test]
+fn junit_snapshot() {
+    let junit = JunitRenderer { left: LEFT.to_string(), right: RIGHT.to_string(), fail_on: None };
+    insta::assert_snapshot!(render(&junit, &sample_diff()));
+}
+
+#[test]
+fn junit_fails_only_at_threshold() {
+    let junit = JunitRenderer { left: LEFT.to_string(), right: RIGHT.to_string(), fail_on: Some(Severity::Error) };
+    let xml = render(&junit, &sample_diff());
+    assert!(xml.contains("<testsuites name=\"arxml-diff\" tests=\"2\" failures=\"0\">"));
+    assert!(!xml.contains("<failure"));
+}
+
+#[test]
+fn multi_file_reports_name_members() {
+    let docs = |path: &str| vec![("System.arxml".to_string(), parse_file(path).unwrap())];
+    let mut diff = compute_multi_diff(&docs(LEFT), &docs(RIGHT), &DiffOptions::default());
+    Policy::autosar().apply(&mut diff);
+
+    let sarif = render(&SarifRenderer { left: "left.zip".to_string(), right: "right.zip".to_string() }, &diff);
+    let log: serde_json::Value = serde_json::from_str(&sarif).unwrap();
+    let uris: Vec<_> = log["runs"][0]["results"].as_array().unwrap().iter()
+        .map(|r| r["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(uris, vec!["left.zip!/System.arxml", "right.zip!/System.arxml"]);
+
+    let junit = JunitRenderer { left: "left.zip".to_string(), right: "right.zip".to_string(), fail_on: None };
+    assert!(render(&junit, &diff).contains("<testsuite name=\"System.arxml\" tests=\"2\" failures=\"2\">"));
+}
+
+#[test]
+fn paths_lists_one_line_per_change() {
+    let doc = |elements: &str| parse_str(&format!(
+        "<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>{}</ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>", elements
+    )).unwrap();
+    let left = doc("<I-SIGNAL><SHORT-NAME>OldSignal</SHORT-NAME></I-SIGNAL>\
+                    <I-SIGNAL UUID=\"a\"><SHORT-NAME>A</SHORT-NAME><LENGTH>8</LENGTH></I-SIGNAL>\
+                    <I-SIGNAL><SHORT-NAME>Speed</SHORT-NAME><LENGTH>8</LENGTH></I-SIGNAL>");
+    let right = doc("<I-SIGNAL UUID=\"a\"><SHORT-NAME>B</SHORT-NAME><LENGTH>8</LENGTH></I-SIGNAL>\
+                     <I-SIGNAL><SHORT-NAME>Speed</SHORT-NAME><LENGTH>16</LENGTH></I-SIGNAL>\
+                     <SENDER-RECEIVER-INTERFACE><SHORT-NAME>NewIf</SHORT-NAME></SENDER-RECEIVER-INTERFACE>");
+    let mut diff = compute_tree_diff(&left.root, &right.root);
+    Policy::autosar().apply(&mut diff);
+    insta::assert_snapshot!(render(&PathsRenderer, &diff));
+}
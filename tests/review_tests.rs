@@ -0,0 +1,118 @@
+use arxml_diff::diff::compute_tree_diff;
+use arxml_diff::parse::parse_str;
+use arxml_diff::review::{review_key, reviewables, Review};
+use arxml_diff::tui::App;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::path::PathBuf;
+
+mod common;
+
+use common::{press, sample_diff, screen, shows, LEFT, RIGHT};
+
+/// A fresh sidecar file path for one test.
+fn sidecar(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("arxml-diff-review-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create temp dir");
+    let path = dir.join("review.json");
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+#[test]
+fn keys_use_autosar_paths_and_number_unnamed_siblings() {
+    let left = parse_str(r#"<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>
+        <I-SIGNAL><SHORT-NAME>Speed</SHORT-NAME><DESC>a</DESC><DESC>b</DESC></I-SIGNAL>
+        </ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>"#).expect("parse");
+    let right = parse_str(r#"<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>
+        <I-SIGNAL><SHORT-NAME>Speed</SHORT-NAME><DESC>a</DESC><DESC>c</DESC></I-SIGNAL>
+        <I-SIGNAL><SHORT-NAME>Torque</SHORT-NAME></I-SIGNAL>
+        </ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>"#).expect("parse");
+    let diff = compute_tree_diff(&left.root, &right.root);
+    let keys: Vec<String> = reviewables(&diff).into_iter().map(|r| r.key).collect();
+    assert_eq!(keys, ["/Pkg/Speed/DESC[2]", "/Pkg/Torque"]);
+    assert_eq!(review_key(&diff, &[]), "/AUTOSAR");
+    assert_eq!(review_key(&diff, &[0]), "/AUTOSAR/AR-PACKAGES");
+    assert_eq!(review_key(&diff, &[0, 0]), "/Pkg");
+}
+
+#[test]
+fn marks_are_saved_and_restored_for_the_same_inputs_only() {
+    let path = sidecar("persist");
+    let mut review = Review::open(&path, LEFT, RIGHT).expect("open review");
+    assert!(review.marks().is_empty());
+    review.set_reviewed("/MyEcu/CompA", true);
+    review.set_note("/MyEcu/CompA", "checked with the ECU team");
+    review.save().expect("save review");
+
+    let restored = Review::open(&path, LEFT, RIGHT).expect("reopen review");
+    assert!(restored.is_reviewed("/MyEcu/CompA"));
+    assert_eq!(restored.note("/MyEcu/CompA"), Some("checked with the ECU team"));
+
+    // other inputs start a review of their own in the same file
+    let mut swapped = Review::open(&path, RIGHT, LEFT).expect("open other review");
+    assert!(swapped.marks().is_empty());
+    swapped.set_reviewed("/MyEcu/CompC", true);
+    swapped.save().expect("save other review");
+    assert!(Review::open(&path, LEFT, RIGHT).expect("reopen review").is_reviewed("/MyEcu/CompA"));
+
+    let json = std::fs::read_to_string(&path).expect("sidecar file");
+    assert_eq!(json.matches("\"sha256\"").count(), 4, "{}", json);
+}
+
+#[test]
+fn report_lists_changes_with_their_marks_and_notes() {
+    let diff = sample_diff();
+    let mut review = Review::open(sidecar("report"), LEFT, RIGHT).expect("open review");
+    review.set_reviewed("/MyEcu/CompA", true);
+    review.set_note("/MyEcu/CompA", "expected");
+    review.set_note("/MyEcu/CompB", "ask about this one");
+    let mut out = Vec::new();
+    review.write_report(&diff, &mut out).expect("report");
+    let report = String::from_utf8(out).expect("utf-8");
+    assert_eq!(report, format!("Review of {} and {}
+1/2 changes reviewed
+
+[x] removed COMPONENT /MyEcu/CompA [warning]
+    expected
+[ ] added COMPONENT /MyEcu/CompC [info]
+
+Other notes:
+/MyEcu/CompB: ask about this one
+", LEFT, RIGHT));
+}
+
+#[test]
+fn tui_marks_changes_and_subtrees_and_shows_progress() {
+    let diff = sample_diff();
+    let path = sidecar("tui");
+    let review = Review::open(&path, LEFT, RIGHT).expect("open review");
+    let mut app = App::new(&diff, &[]).with_review(review);
+    assert!(shows(&mut app, "0/2 reviewed"));
+
+    // the first change, then a note on it
+    press(&mut app, "E]m");
+    let shown = screen(&mut app).join("\n");
+    assert!(shown.contains("1/2 reviewed"), "{}", shown);
+    assert!(shown.contains("CompA [warning] ✓"), "{}", shown);
+    press(&mut app, "aok");
+    app.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert!(shows(&mut app, "note: ok"));
+
+    // u goes to the next unreviewed change; M on an ancestor marks its subtree
+    press(&mut app, "u");
+    assert_eq!(app.selected().key.as_deref(), Some("CompC"));
+    press(&mut app, "1M");
+    assert!(shows(&mut app, "2/2 reviewed"));
+    // and again takes the marks off
+    press(&mut app, "M");
+    assert!(shows(&mut app, "0/2 reviewed"));
+    press(&mut app, "]m");
+
+    // marks are saved as they are made
+    let restored = Review::open(&path, LEFT, RIGHT).expect("reopen review");
+    assert!(restored.is_reviewed("/MyEcu/CompA"));
+    assert!(!restored.is_reviewed("/MyEcu/CompC"));
+    assert_eq!(restored.note("/MyEcu/CompA"), Some("ok"));
+    let mut app = App::new(&diff, &[]).with_review(restored);
+    assert!(shows(&mut app, "1/2 reviewed"));
+}
@@ -0,0 +1,68 @@
+use arxml_diff::parse::{parse_document, parse_str, ContentNode, Document};
+
+fn reparse(doc: &Document, name: &str) -> Document {
+    let path = std::env::temp_dir().join(format!("arxml-diff-{}-{}.arxml", name, std::process::id()));
+    std::fs::write(&path, doc.serialize()).expect("write serialized");
+    let again = parse_document(path.to_str().unwrap()).expect("reparse serialized");
+    let _ = std::fs::remove_file(&path);
+    again
+}
+
+#[test]
+fn round_trip_yields_identical_tree() {
+    for fixture in ["roundtrip", "prefixed", "sample-left", "sample-right"] {
+        let doc = parse_document(&format!("tests/fixtures/{}.arxml", fixture)).expect("parse fixture");
+        assert_eq!(reparse(&doc, fixture), doc, "round trip of {}", fixture);
+    }
+}
+
+#[test]
+fn content_after_the_root_is_kept() {
+    let doc = parse_document("tests/fixtures/roundtrip.arxml").expect("parse");
+    assert_eq!(doc.epilog, vec![
+        ContentNode::Comment(" end of export ".to_string()),
+        ContentNode::ProcessingInstruction("tool-marker done".to_string()),
+    ]);
+    assert!(doc.serialize().ends_with("</AUTOSAR>\n<!-- end of export -->\n<?tool-marker done?>"));
+    assert_eq!(reparse(&doc, "epilog").epilog, doc.epilog);
+}
+
+#[test]
+fn serialize_is_deterministic_and_keeps_source_order() {
+    let doc = parse_document("tests/fixtures/roundtrip.arxml").expect("parse");
+    let first = doc.serialize();
+    assert_eq!(first, doc.clone().serialize());
+
+    assert!(first.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(first.contains(
+        "<AUTOSAR xmlns=\"http://autosar.org/schema/r4.0\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation="
+    ));
+    assert!(first.contains("<AR-PACKAGE UUID=\"pkg-1\" T=\"2024-01-01T00:00:00Z\">\n      <SHORT-NAME>Pkg</SHORT-NAME>\n      <ELEMENTS>"));
+    // SHORT-NAME goes back where it was, even when it is not the first child
+    assert!(first.contains("<SENDER-RECEIVER-INTERFACE>\n          <ADMIN-DATA/>\n          <SHORT-NAME>SpeedIf</SHORT-NAME>\n        </SENDER-RECEIVER-INTERFACE>"));
+    assert!(first.contains("Speed &lt; 120 &amp; rising"));
+}
+
+#[test]
+fn fragments_carry_inherited_namespace_declarations() {
+    let doc = parse_document("tests/fixtures/roundtrip.arxml").expect("parse");
+    let scope = doc.root.child_scope(&[]);
+    let packages = &doc.root.children[0];
+    let xml = packages.serialize_in_scope(&scope);
+    assert!(xml.starts_with(
+        "<AR-PACKAGES xmlns=\"http://autosar.org/schema/r4.0\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">"
+    ));
+    // nested elements do not repeat the declarations
+    assert_eq!(xml.matches("xmlns=").count(), 1);
+}
+
+#[test]
+fn short_name_keeps_its_own_prefix_and_attributes() {
+    let xml = r#"<AUTOSAR xmlns="http://autosar.org/schema/r4.0" xmlns:ar="http://autosar.org/schema/r4.0"><AR-PACKAGES><AR-PACKAGE><ar:SHORT-NAME BLUEPRINT-VALUE="x">Pkg</ar:SHORT-NAME></AR-PACKAGE></AR-PACKAGES></AUTOSAR>"#;
+    let mut doc = parse_str(xml).expect("parse");
+    assert!(doc.serialize().contains(r#"<AR-PACKAGE><ar:SHORT-NAME BLUEPRINT-VALUE="x">Pkg</ar:SHORT-NAME></AR-PACKAGE>"#));
+
+    // A renamed element keeps the tag and attributes of its SHORT-NAME
+    doc.root.children[0].children[0].short_name = Some("Renamed".to_string());
+    assert!(doc.serialize().contains(r#"<AR-PACKAGE><ar:SHORT-NAME BLUEPRINT-VALUE="x">Renamed</ar:SHORT-NAME></AR-PACKAGE>"#));
+}
@@ -0,0 +1,88 @@
+use arxml_diff::diff::{compute_tree_diff, DiffNode};
+use arxml_diff::parse::parse_str;
+use arxml_diff::stats::{Counts, Stats};
+use std::process::Command;
+
+fn package(name: &str, elements: &str) -> String {
+    format!("<AR-PACKAGE><SHORT-NAME>{}</SHORT-NAME><ELEMENTS>{}</ELEMENTS></AR-PACKAGE>", name, elements)
+}
+
+fn signal(name: &str, length: u32) -> String {
+    format!("<I-SIGNAL><SHORT-NAME>{}</SHORT-NAME><LENGTH>{}</LENGTH></I-SIGNAL>", name, length)
+}
+
+fn diff(left: &str, right: &str) -> DiffNode {
+    let doc = |packages: &str| parse_str(&format!("<AUTOSAR><AR-PACKAGES>{}</AR-PACKAGES></AUTOSAR>", packages)).unwrap();
+    compute_tree_diff(&doc(left).root, &doc(right).root)
+}
+
+fn stats(left: &str, right: &str) -> Stats {
+    Stats::of(&diff(left, right))
+}
+
+#[test]
+fn counts_changes_by_tag_and_package() {
+    let left = package("Signals", &format!("{}{}{}", signal("Speed", 8), signal("Rpm", 8), signal("Old", 8)))
+        + &package("Interfaces", "");
+    let right = package("Signals", &format!("{}{}{}", signal("Speed", 16), signal("Rpm", 16), signal("New", 8)))
+        + &package("Interfaces", "<SENDER-RECEIVER-INTERFACE><SHORT-NAME>SpeedIf</SHORT-NAME></SENDER-RECEIVER-INTERFACE>");
+    let s = stats(&left, &right);
+    assert_eq!(s.totals, Counts { added: 2, removed: 1, changed: 2, moved: 0 });
+    assert_eq!(s.by_tag, vec![
+        ("I-SIGNAL".to_string(), Counts { added: 1, removed: 1, ..Counts::default() }),
+        ("LENGTH".to_string(), Counts { changed: 2, ..Counts::default() }),
+        ("SENDER-RECEIVER-INTERFACE".to_string(), Counts { added: 1, ..Counts::default() }),
+    ]);
+    assert_eq!(s.packages.iter().map(|(p, c)| (p.as_str(), c.total())).collect::<Vec<_>>(), vec![("/Signals", 4), ("/Interfaces", 1)]);
+}
+
+#[test]
+fn an_added_package_is_counted_in_its_parent_package() {
+    let top = |packages: &str| format!("<AR-PACKAGE><SHORT-NAME>Top</SHORT-NAME><AR-PACKAGES>{}</AR-PACKAGES></AR-PACKAGE>", packages);
+    let s = stats(&top(""), &top(&package("Sub", &signal("Speed", 8))));
+    assert_eq!(s.totals, Counts { added: 1, ..Counts::default() });
+    assert_eq!(s.packages, vec![("/Top".to_string(), Counts { added: 1, ..Counts::default() })]);
+}
+
+#[test]
+fn a_removed_and_added_element_with_the_same_key_moved() {
+    let left = package("A", &signal("Speed", 8)) + &package("B", "");
+    let right = package("A", "") + &package("B", &signal("Speed", 8));
+    let s = stats(&left, &right);
+    assert_eq!(s.totals, Counts { moved: 1, ..Counts::default() });
+    // counted where the element ends up
+    assert_eq!(s.packages, vec![("/B".to_string(), Counts { moved: 1, ..Counts::default() })]);
+    // and counted the same in the summary of --brief
+    assert_eq!(diff(&left, &right).summary().to_string(), "0 added, 0 removed, 0 changed, 1 moved");
+}
+
+#[test]
+fn each_removed_element_moves_at_most_once() {
+    let speeds = |n: usize| (0..n).map(|_| signal("Speed", 8)).collect::<String>();
+    let left = package("A", &speeds(3)) + &package("B", "");
+    let right = package("A", "") + &package("B", &speeds(2));
+    assert_eq!(stats(&left, &right).totals, Counts { removed: 1, moved: 2, ..Counts::default() });
+}
+
+#[test]
+fn stats_subcommand_and_summary_footer() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_arxml-diff"))
+            .args(args)
+            .args(["tests/fixtures/sample-left.arxml", "tests/fixtures/sample-right.arxml"])
+            .output().expect("run arxml-diff")
+    };
+    let out = run(&["stats"]);
+    assert_eq!(out.status.code(), Some(1));
+    let text = String::from_utf8(out.stdout).unwrap();
+    assert!(text.starts_with("Elements: 8 left, 8 right\nChanges:  1 added, 1 removed, 0 changed, 0 moved\nTiming:   parsed in "), "{}", text);
+    assert!(text.contains("Changes by tag:\n  COMPONENT      2  (1 added, 1 removed, 0 changed, 0 moved)\n"), "{}", text);
+
+    let text = String::from_utf8(run(&["--summary"]).stdout).unwrap();
+    assert!(text.contains("+ CompC [info]\n\nElements: 8 left, 8 right\n"), "{}", text);
+
+    // machine-readable output stays parseable
+    let out = run(&["--format", "paths", "--summary"]);
+    assert!(!String::from_utf8(out.stdout).unwrap().contains("Elements:"));
+    assert!(String::from_utf8(out.stderr).unwrap().starts_with("Elements:"));
+}
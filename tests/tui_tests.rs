@@ -0,0 +1,265 @@
+use arxml_diff::diff::{compute_tree_diff, compute_tree_diff_with, DiffOptions};
+use arxml_diff::parse::parse_str;
+use arxml_diff::tui::{Action, App};
+use crossterm::event::{KeyCode, KeyEvent};
+
+mod common;
+
+use common::{press, sample_diff, screen, screen_sized, shows};
+
+#[test]
+fn navigates_and_expands_like_a_file_tree() {
+    let diff = sample_diff();
+    let mut app = App::new(&diff, &[]);
+    assert_eq!(app.selected().tag, "ARXML");
+    assert!(shows(&mut app, "▸ MyEcu"));
+    assert!(!shows(&mut app, "COMPONENTS"));
+
+    // j moves down, l opens a node and then goes into it
+    press(&mut app, "j");
+    assert_eq!(app.selected().key.as_deref(), Some("MyEcu"));
+    press(&mut app, "ll");
+    assert_eq!(app.selected().tag, "COMPONENTS");
+    press(&mut app, "l");
+    assert!(shows(&mut app, "CompA [warning]"));
+    assert!(shows(&mut app, "CompC [info]"));
+
+    // h collapses the open node, then goes to its parent
+    press(&mut app, "h");
+    assert!(!shows(&mut app, "CompA"));
+    press(&mut app, "h");
+    assert_eq!(app.selected().key.as_deref(), Some("MyEcu"));
+
+    // ] jumps to the next change, expanding the path to it
+    press(&mut app, "C]");
+    assert_eq!(app.selected().key.as_deref(), Some("CompA"));
+    assert!(shows(&mut app, "change 1/2"));
+    press(&mut app, "]");
+    assert_eq!(app.selected().key.as_deref(), Some("CompC"));
+
+    assert_eq!(press(&mut app, "q"), Action::Quit);
+}
+
+#[test]
+fn toggles_the_side_by_side_view_and_the_raw_pane() {
+    let diff = sample_diff();
+    let mut app = App::new(&diff, &[]);
+    press(&mut app, "E]");
+    assert!(shows(&mut app, "ARXML Diff (Unified)"));
+
+    press(&mut app, "v");
+    let lines = screen(&mut app);
+    assert!(!lines.iter().any(|l| l.contains("ARXML Diff (Unified)")));
+    assert!(lines.iter().any(|l| l.contains("Left") && l.contains("Right")));
+    // CompA was removed: it is on the left only
+    let row: Vec<char> = lines.iter().find(|l| l.contains("CompA")).expect("CompA row").chars().collect();
+    let (left, right) = row.split_at(row.len() / 2);
+    assert!(String::from_iter(left).contains("CompA"));
+    assert!(String::from_iter(right).trim_matches(|c: char| c == '│' || c == ' ').is_empty());
+
+    press(&mut app, "r");
+    assert!(shows(&mut app, "Raw XML"));
+    assert!(shows(&mut app, "<SHORT-NAME>CompA</SHORT-NAME>"));
+    press(&mut app, "r");
+    assert!(!shows(&mut app, "Raw XML"));
+}
+
+#[test]
+fn exports_the_selected_node() {
+    let diff = sample_diff();
+    let dir = std::env::temp_dir().join(format!("arxml-diff-tui-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create export dir");
+    let mut app = App::new(&diff, &[]).with_export_dir(&dir);
+    press(&mut app, "E]c");
+
+    let status = app.status().expect("status message").to_string();
+    assert!(status.starts_with("Exported to "), "{}", status);
+    let exported = std::fs::read_to_string(status.trim_start_matches("Exported to ")).expect("exported file");
+    assert!(exported.contains("<!-- Left -->"));
+    assert!(exported.contains("<SHORT-NAME>CompA</SHORT-NAME>"));
+    assert!(exported.contains("<!-- Right -->\n<none>"));
+    assert!(shows(&mut app, "Exported to "));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn search_selects_and_keeps_the_match() {
+    let diff = sample_diff();
+    let messages = vec!["note: schema versions differ".to_string()];
+    let mut app = App::new(&diff, &messages);
+    press(&mut app, "/compc");
+    app.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(app.selected().key.as_deref(), Some("CompC"));
+    let lines = screen(&mut app);
+    assert!(lines.iter().any(|l| l.contains("/compc")));
+    assert!(lines.iter().any(|l| l.contains("note: schema versions differ")));
+}
+
+#[test]
+fn search_by_path_matches_the_named_element_only() {
+    let doc = |sdg: &str| parse_str(&format!(
+        "<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>\
+         <SENDER-RECEIVER-INTERFACE><SHORT-NAME>SpeedIf</SHORT-NAME>\
+         <ADMIN-DATA><SDGS><SDG GID=\"rev\">{}</SDG></SDGS></ADMIN-DATA>\
+         </SENDER-RECEIVER-INTERFACE></ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>", sdg
+    )).unwrap();
+    let diff = compute_tree_diff(&doc("1").root, &doc("2").root);
+    let mut app = App::new(&diff, &[]);
+    // ADMIN-DATA, SDGS and SDG are in /Pkg/SpeedIf too, but are not named by it
+    press(&mut app, "/speedif");
+    app.handle_key(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(app.selected().key.as_deref(), Some("SpeedIf"));
+    assert!(shows(&mut app, "1/1"));
+}
+
+#[test]
+fn details_show_uuids_and_changed_values_of_the_selection() {
+    let signal = |uuid: &str, t: &str, length: u32| parse_str(&format!(
+        "<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>\
+         <I-SIGNAL UUID=\"{}\" T=\"{}\"><SHORT-NAME>Speed</SHORT-NAME><LENGTH>{}</LENGTH></I-SIGNAL>\
+         </ELEMENTS></AR-PACKAGE></AR-PACKAGES></AUTOSAR>", uuid, t, length)).expect("parse");
+    let diff = compute_tree_diff(&signal("u1", "a", 8).root, &signal("u2", "b", 16).root);
+    let mut app = App::new(&diff, &[]);
+    press(&mut app, "E]");
+    assert_eq!(app.selected().tag, "I-SIGNAL");
+    assert!(shows(&mut app, "Path: /Pkg/Speed    Tag: I-SIGNAL    Key: Speed"));
+    assert!(shows(&mut app, "UUID: u1 -> u2"));
+    assert!(shows(&mut app, "Children: 0 added, 0 removed, 1 changed, 0 unchanged"));
+    assert_eq!(row_of(&mut app, "@T").as_deref(), Some("│@T a b │"));
+
+    press(&mut app, "]");
+    assert_eq!(app.selected().tag, "LENGTH");
+    assert_eq!(row_of(&mut app, "text").as_deref(), Some("│text 8 16 │"));
+}
+
+/// The row of the screen containing `what`, with runs of spaces collapsed.
+fn row_of(app: &mut App, what: &str) -> Option<String> {
+    screen(app).into_iter().find(|l| l.contains(what)).map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[test]
+fn the_raw_pane_is_built_once_per_node_and_view() {
+    let diff = sample_diff();
+    let mut app = App::new(&diff, &[]);
+    press(&mut app, "E]r");
+    screen(&mut app);
+    screen(&mut app);
+    assert_eq!(app.raw_builds(), 1);
+    // scrolling keeps it, another node or view builds it again
+    app.handle_key(KeyEvent::from(KeyCode::PageDown));
+    screen(&mut app);
+    assert_eq!(app.raw_builds(), 1);
+    press(&mut app, "]");
+    screen(&mut app);
+    assert_eq!(app.raw_builds(), 2);
+    press(&mut app, "v");
+    screen(&mut app);
+    screen(&mut app);
+    assert_eq!(app.raw_builds(), 3);
+}
+
+#[test]
+fn the_raw_pane_shows_comment_changes() {
+    let doc = |comment: &str| parse_str(&format!("<AUTOSAR><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><!--{}--></AR-PACKAGE></AR-PACKAGES></AUTOSAR>", comment)).expect("parse");
+    let opts = DiffOptions::builder().include_comments(true).build();
+    let diff = compute_tree_diff_with(&doc(" generated ").root, &doc(" edited by hand ").root, &opts);
+    let mut app = App::new(&diff, &[]);
+    press(&mut app, "E]r");
+    assert_eq!(app.selected().tag, "AR-PACKAGE");
+    assert!(shows(&mut app, "-    <!-- generated -->"));
+    assert!(shows(&mut app, "+    <!-- edited by hand -->"));
+}
+
+#[test]
+fn the_filter_cycles_and_the_title_counts_changes() {
+    let diff = sample_diff();
+    let mut app = App::new(&diff, &[]);
+    press(&mut app, "E");
+    let shown = |app: &mut App| {
+        let lines = screen(app);
+        ["CompA", "CompB", "CompC"].into_iter().filter(|name| lines.iter().any(|l| l.contains(name))).collect::<Vec<_>>()
+    };
+    assert!(shows(&mut app, " +1 -1 ~0 ↔0  filter: all "));
+    assert_eq!(shown(&mut app), ["CompA", "CompB", "CompC"]);
+    for (filter, names) in [
+        ("changes", &["CompA", "CompC"][..]),
+        ("added only", &["CompC"]),
+        ("removed only", &["CompA"]),
+        ("changed only", &[]),
+        ("all", &["CompA", "CompB", "CompC"]),
+    ] {
+        press(&mut app, "f");
+        assert!(shows(&mut app, &format!(" +1 -1 ~0 ↔0  filter: {} ", filter)), "{}", filter);
+        assert_eq!(shown(&mut app), names, "{}", filter);
+    }
+
+    // changes of other kinds are skipped, and the counter says which change is selected
+    press(&mut app, "ff]");
+    assert_eq!(app.selected().key.as_deref(), Some("CompC"));
+    assert!(shows(&mut app, " change 1/1  +1 -1 ~0 ↔0  filter: added only "));
+}
+
+#[test]
+fn digits_expand_to_a_depth_and_keep_the_selection() {
+    let diff = sample_diff();
+    let mut app = App::new(&diff, &[]);
+    let selected = |app: &App| app.selected().key.clone().unwrap_or_else(|| app.selected().tag.clone());
+
+    press(&mut app, "3");
+    assert!(shows(&mut app, "CompB"));
+    press(&mut app, "2");
+    assert!(shows(&mut app, "COMPONENTS") && !shows(&mut app, "CompB"));
+    press(&mut app, "1");
+    assert!(shows(&mut app, "MyEcu") && !shows(&mut app, "COMPONENTS"));
+
+    // the selection stays on its node while it is shown, and moves up to the nearest
+    // shown ancestor when it is not
+    press(&mut app, "3jjjj");
+    assert_eq!(selected(&app), "CompB");
+    press(&mut app, "E");
+    assert_eq!(selected(&app), "CompB");
+    press(&mut app, "2");
+    assert_eq!(selected(&app), "COMPONENTS");
+    press(&mut app, "C");
+    assert_eq!(selected(&app), "MyEcu");
+    assert!(!shows(&mut app, "COMPONENTS"));
+    press(&mut app, "E");
+    assert_eq!(selected(&app), "MyEcu");
+    assert!(shows(&mut app, "CompA") && shows(&mut app, "CompB") && shows(&mut app, "CompC"));
+}
+
+/// The rows of the tree pane, drawn tall enough to show them all.
+fn tree_rows(app: &mut App) -> Vec<String> {
+    screen_sized(app, 60, 60).into_iter().skip(2).take_while(|l| !l.trim_start().starts_with('└')).collect()
+}
+
+#[test]
+fn rows_updated_in_place_match_a_full_rebuild() {
+    let doc = |length: u32, signal: &str| parse_str(&format!(
+        "<AUTOSAR><AR-PACKAGES>\
+         <AR-PACKAGE><SHORT-NAME>Pkg</SHORT-NAME><ELEMENTS>\
+         <I-SIGNAL><SHORT-NAME>Speed</SHORT-NAME><LENGTH>{length}</LENGTH></I-SIGNAL>\
+         <I-SIGNAL><SHORT-NAME>Gear</SHORT-NAME><LENGTH>4</LENGTH></I-SIGNAL>\
+         <I-SIGNAL><SHORT-NAME>{signal}</SHORT-NAME><LENGTH>2</LENGTH></I-SIGNAL>\
+         </ELEMENTS><AR-PACKAGES><AR-PACKAGE><SHORT-NAME>Sub</SHORT-NAME><ELEMENTS>\
+         <I-SIGNAL><SHORT-NAME>Rpm</SHORT-NAME><LENGTH>{length}</LENGTH></I-SIGNAL>\
+         </ELEMENTS></AR-PACKAGE></AR-PACKAGES></AR-PACKAGE>\
+         </AR-PACKAGES></AUTOSAR>", length = length, signal = signal)).expect("parse");
+    let diff = compute_tree_diff(&doc(8, "Old").root, &doc(16, "New").root);
+    // open and close nodes, and reveal changes and search matches deep in the tree,
+    // with all nodes shown and with changes only
+    for filter in ["", "f"] {
+        let mut app = App::new(&diff, &[]);
+        press(&mut app, filter);
+        for keys in ["jl", "jlj", "l", "jjjl", "h", "kkkh", "]", "]]]", "khh", "/rpm\n", "C", "n", "[", "jj\n"] {
+            for c in keys.chars() {
+                let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+                app.handle_key(KeyEvent::from(code));
+            }
+            let in_place = tree_rows(&mut app);
+            // going through all filters builds the rows again from the expanded nodes
+            press(&mut app, "fffff");
+            assert_eq!(tree_rows(&mut app), in_place, "after {:?} with filter {:?}", keys, filter);
+        }
+    }
+}